@@ -0,0 +1,463 @@
+#![warn(missing_docs)]
+
+//! PUImURI trainer for Ohms law, power equation and their combinations
+//!
+//! User accounts with argon2 hashed passwords or an external identity provider, roles and login tokens
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use eyre::{eyre, Result};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::TrainerError;
+
+/// Shortest accepted password
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// Longest accepted username
+pub const MAX_USERNAME_LENGTH: usize = 64;
+
+/// Hex digits of the suffix that tells apart users of an identity provider with the same name
+const SUFFIX_LENGTH: usize = 8;
+
+/// Seconds a login stays valid, a week
+pub const LOGIN_TTL: u64 = 7 * 24 * 60 * 60;
+
+/// Hash with the same parameters as hash_password(), checked for unknown users so that logins take as long as for known ones
+const DUMMY_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$KuacoXcQNcC6GeqysGh0Ng$+ICB4xd7GlYLI8W0IIOvqTYLGRGyudgA5xqKvcTREqM";
+
+/// What a user may do, every role may also do everything the roles before it may
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    /// Trains, takes exams and answers assignments
+    #[default]
+    Student,
+    /// Manages classes, assignments and exams
+    Teacher,
+    /// Manages users
+    Admin,
+}
+
+impl Role {
+    /// Whether the role may do what the required role may
+    pub fn allows(&self, required: Role) -> bool {
+        *self >= required
+    }
+}
+
+impl FromStr for Role {
+    type Err = TrainerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "student" => Ok(Role::Student),
+            "teacher" => Ok(Role::Teacher),
+            "admin" => Ok(Role::Admin),
+            _ => Err(TrainerError::InvalidAccount(format!(
+                "\"{}\" is not a role",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Hashes the password with argon2 and a random salt
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::encode_b64(&rand::thread_rng().gen::<[u8; 16]>())
+        .map_err(|error| eyre!("Unable to create a salt: {}", error))?;
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|error| eyre!("Unable to hash the password: {}", error))?;
+    Ok(hash.to_string())
+}
+
+/// Whether the password matches the hash, a malformed hash matches nothing
+pub fn verify_password(hash: &str, password: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+/// Whether the password matches the hash of a user, None and the empty hash of provider users match nothing
+///
+/// Without a hash of its own the password is checked against a dummy hash, so that the time taken does not tell whether
+/// the user exists.
+pub fn verify_login(hash: Option<&str>, password: &str) -> bool {
+    match hash.filter(|hash| !hash.is_empty()) {
+        Some(hash) => verify_password(hash, password),
+        None => {
+            verify_password(DUMMY_HASH, password);
+            false
+        }
+    }
+}
+
+/// Hash a login token is stored by, so that a leaked database does not leak logins
+pub fn token_hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Longest start of the text with at most max_length bytes that does not split a character
+fn shorten(text: &str, max_length: usize) -> &str {
+    let mut end = text.len().min(max_length);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Local user
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct User {
+    /// Random identifier of the user
+    pub id: String,
+    /// Name the user logs in with
+    pub username: String,
+    /// What the user may do
+    pub role: Role,
+    /// Argon2 hash of the password in PHC format, empty for users of an identity provider
+    #[serde(skip_serializing)]
+    pub password_hash: String,
+    /// Identity of the user at the identity provider they log in with, e.g. "https://idp.example.com#248289761001"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    /// When the account was created, in seconds since the Unix epoch
+    pub created_at: u64,
+}
+
+/// Logged in client, known by the hash of the token in its cookie
+#[derive(Clone, Debug, PartialEq)]
+pub struct Login {
+    /// Hash of the login token, see token_hash()
+    pub token_hash: String,
+    /// User that logged in
+    pub user_id: String,
+    /// When the login expires, in seconds since the Unix epoch
+    pub expires_at: u64,
+}
+
+/// Users and their logins
+#[derive(Clone, Debug, Default)]
+pub struct Accounts {
+    users: Vec<User>,
+    logins: Vec<Login>,
+}
+
+impl Accounts {
+    /// Creates an account, usernames are unique regardless of case
+    pub fn register(
+        &mut self,
+        username: &str,
+        password: &str,
+        role: Role,
+        now: u64,
+    ) -> Result<&User> {
+        self.check_new_user(username, password)?;
+        let password_hash = hash_password(password)?;
+        self.add_user(username, password_hash, role, now)
+    }
+
+    /// Checks that an account can be created with the username and password before the password is hashed
+    pub fn check_new_user(&self, username: &str, password: &str) -> Result<()> {
+        let username = username.trim();
+        if username.is_empty() || username.len() > MAX_USERNAME_LENGTH {
+            return Err(TrainerError::InvalidAccount(format!(
+                "Username must have 1 to {} characters",
+                MAX_USERNAME_LENGTH
+            ))
+            .into());
+        }
+        if password.chars().count() < MIN_PASSWORD_LENGTH {
+            return Err(TrainerError::InvalidAccount(format!(
+                "Password must have at least {} characters",
+                MIN_PASSWORD_LENGTH
+            ))
+            .into());
+        }
+        if self.find(username).is_some() {
+            return Err(TrainerError::UsernameTaken(username.to_string()).into());
+        }
+        Ok(())
+    }
+
+    /// Creates an account checked with check_new_user(), the username may have been taken since
+    pub fn add_user(
+        &mut self,
+        username: &str,
+        password_hash: String,
+        role: Role,
+        now: u64,
+    ) -> Result<&User> {
+        let username = username.trim();
+        if self.find(username).is_some() {
+            return Err(TrainerError::UsernameTaken(username.to_string()).into());
+        }
+        self.insert_user(User {
+            id: format!("{:032x}", rand::thread_rng().gen::<u128>()),
+            username: username.to_string(),
+            role,
+            password_hash,
+            external_id: None,
+            created_at: now,
+        });
+        Ok(self.users.last().unwrap())
+    }
+
+    /// Student known by an identity provider, None before their first login
+    pub fn external_user(&self, external_id: &str) -> Option<&User> {
+        self.users
+            .iter()
+            .find(|user| user.external_id.as_deref() == Some(external_id))
+    }
+
+    /// Student for the first login through an identity provider, added with add_external_user() once saved
+    ///
+    /// The username suggested by the provider is shortened to MAX_USERNAME_LENGTH and gets a suffix if a different user already has it.
+    pub fn new_external_user(&self, external_id: &str, username: &str, now: u64) -> User {
+        let mut username = shorten(username.trim(), MAX_USERNAME_LENGTH).to_string();
+        if username.is_empty() || self.find(&username).is_some() {
+            let base = shorten(&username, MAX_USERNAME_LENGTH - SUFFIX_LENGTH - 1).to_string();
+            let mut suffix = token_hash(external_id)[..SUFFIX_LENGTH].to_string();
+            loop {
+                username = format!("{}-{}", base, suffix);
+                if self.find(&username).is_none() {
+                    break;
+                }
+                suffix = format!("{:08x}", rand::thread_rng().gen::<u32>());
+            }
+        }
+        User {
+            id: format!("{:032x}", rand::thread_rng().gen::<u128>()),
+            username,
+            role: Role::Student,
+            // no hash matches a password, so the user can only log in through the provider
+            password_hash: String::new(),
+            external_id: Some(external_id.to_string()),
+            created_at: now,
+        }
+    }
+
+    /// Adds a user from new_external_user(), the provider may have logged them in or the username may have been taken since
+    pub fn add_external_user(&mut self, user: User) -> Result<&User> {
+        let external_id = user.external_id.clone().unwrap_or_default();
+        if let Some(position) = self
+            .users
+            .iter()
+            .position(|existing| existing.external_id.as_deref() == Some(&external_id))
+        {
+            return Ok(&self.users[position]);
+        }
+        if self.find(&user.username).is_some() {
+            return Err(TrainerError::UsernameTaken(user.username).into());
+        }
+        self.insert_user(user);
+        Ok(self.users.last().unwrap())
+    }
+
+    /// Adds an existing user, e.g. one loaded from the database
+    pub fn insert_user(&mut self, user: User) {
+        self.users.retain(|existing| existing.id != user.id);
+        self.users.push(user);
+    }
+
+    /// Adds an existing login, e.g. one loaded from the database
+    pub fn insert_login(&mut self, login: Login) {
+        self.logins.push(login);
+    }
+
+    /// User with the username
+    pub fn find(&self, username: &str) -> Option<&User> {
+        self.users
+            .iter()
+            .find(|user| user.username.eq_ignore_ascii_case(username.trim()))
+    }
+
+    /// Every user in the order they were added
+    pub fn users(&self) -> &[User] {
+        &self.users
+    }
+
+    /// Changes the role of the user
+    pub fn set_role(&mut self, username: &str, role: Role) -> Result<&User, TrainerError> {
+        let username = username.trim();
+        let user = self
+            .users
+            .iter_mut()
+            .find(|user| user.username.eq_ignore_ascii_case(username))
+            .ok_or_else(|| TrainerError::UnknownUser(username.to_string()))?;
+        user.role = role;
+        Ok(user)
+    }
+
+    /// Checks the password and starts a login, returns the token for the cookie together with the login
+    pub fn login(&mut self, username: &str, password: &str, now: u64) -> Result<(String, Login)> {
+        let user = self.find(username);
+        if !verify_login(user.map(|user| user.password_hash.as_str()), password) {
+            return Err(TrainerError::LoginFailed.into());
+        }
+        let user_id = user.unwrap().id.clone();
+        Ok(self.start_login(&user_id, now))
+    }
+
+    /// Starts a login of a user whose identity has already been checked
+    pub fn start_login(&mut self, user_id: &str, now: u64) -> (String, Login) {
+        let mut rng = rand::thread_rng();
+        let token = format!("{:032x}{:032x}", rng.gen::<u128>(), rng.gen::<u128>());
+        let login = Login {
+            token_hash: token_hash(&token),
+            user_id: user_id.to_string(),
+            expires_at: now.saturating_add(LOGIN_TTL),
+        };
+        // expired logins are of no use to anyone
+        self.logins.retain(|login| login.expires_at > now);
+        self.logins.push(login.clone());
+        (token, login)
+    }
+
+    /// User logged in with the token, None if the token is unknown or expired
+    pub fn user(&self, token: &str, now: u64) -> Option<&User> {
+        let hash = token_hash(token);
+        let login = self
+            .logins
+            .iter()
+            .find(|login| login.token_hash == hash && login.expires_at > now)?;
+        self.users.iter().find(|user| user.id == login.user_id)
+    }
+
+    /// Ends the login, returns it if it existed
+    pub fn logout(&mut self, token: &str) -> Option<Login> {
+        let hash = token_hash(token);
+        let position = self
+            .logins
+            .iter()
+            .position(|login| login.token_hash == hash)?;
+        Some(self.logins.remove(position))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roles() {
+        assert!(Role::Admin.allows(Role::Teacher));
+        assert!(Role::Teacher.allows(Role::Teacher));
+        assert!(!Role::Student.allows(Role::Teacher));
+        assert_eq!("Teacher".parse::<Role>().unwrap(), Role::Teacher);
+        assert!("janitor".parse::<Role>().is_err());
+    }
+
+    #[test]
+    fn test_register_and_login() {
+        let mut accounts = Accounts::default();
+        let user = accounts
+            .register("Ada", "correct horse", Role::Teacher, 0)
+            .unwrap()
+            .clone();
+        assert_ne!(user.password_hash, "correct horse");
+        assert!(user.password_hash.starts_with("$argon2"));
+
+        for (username, password) in [("", "correct horse"), ("bob", "short")] {
+            let error = accounts
+                .register(username, password, Role::Student, 0)
+                .unwrap_err();
+            assert!(matches!(
+                error.downcast_ref::<TrainerError>(),
+                Some(TrainerError::InvalidAccount(_))
+            ));
+        }
+        let error = accounts
+            .register("ada", "another one", Role::Student, 0)
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<TrainerError>(),
+            Some(TrainerError::UsernameTaken(_))
+        ));
+
+        for username in ["ada", "nobody"] {
+            let error = accounts.login(username, "wrong horse", 0).unwrap_err();
+            assert!(matches!(
+                error.downcast_ref::<TrainerError>(),
+                Some(TrainerError::LoginFailed)
+            ));
+        }
+        // unknown users are checked against a real hash too
+        assert!(PasswordHash::new(DUMMY_HASH).is_ok());
+        assert!(!verify_login(None, "not the password of anyone"));
+        let (token, login) = accounts.login("ada", "correct horse", 100).unwrap();
+        assert_eq!(login.expires_at, 100 + LOGIN_TTL);
+        assert_ne!(login.token_hash, token);
+        assert_eq!(accounts.user(&token, 200).unwrap().id, user.id);
+        assert!(accounts.user(&token, 100 + LOGIN_TTL).is_none());
+        assert!(accounts.user("guess", 200).is_none());
+
+        assert!(accounts.logout(&token).is_some());
+        assert!(accounts.user(&token, 200).is_none());
+        assert_eq!(
+            accounts.set_role("ADA", Role::Admin).unwrap().role,
+            Role::Admin
+        );
+    }
+
+    #[test]
+    fn test_external_users() {
+        let mut accounts = Accounts::default();
+        accounts
+            .register("ada", "correct horse", Role::Teacher, 0)
+            .unwrap();
+
+        let bob = accounts.new_external_user("idp#1", "bob", 10);
+        assert_eq!(bob.username, "bob");
+        assert_eq!(bob.role, Role::Student);
+        assert_eq!(accounts.external_user("idp#1"), None);
+        assert_eq!(accounts.add_external_user(bob.clone()).unwrap(), &bob);
+        assert_eq!(accounts.external_user("idp#1"), Some(&bob));
+        let robert = accounts.new_external_user("idp#1", "robert", 20);
+        assert_eq!(accounts.add_external_user(robert).unwrap(), &bob);
+        // the provider can not take over a local account by its name
+        let other = accounts.new_external_user("idp#2", "ada", 30);
+        assert_ne!(other.username, "ada");
+        assert!(other.username.starts_with("ada-"));
+        accounts.add_external_user(other).unwrap();
+        assert_eq!(accounts.users().len(), 3);
+        let taken = accounts.new_external_user("idp#3", "carol", 30);
+        accounts
+            .register("Carol", "correct horse", Role::Student, 30)
+            .unwrap();
+        assert!(accounts.add_external_user(taken).is_err());
+
+        // suffixed names stay within the limit and differ from every other one
+        let long = "ä".repeat(MAX_USERNAME_LENGTH);
+        let first = accounts.new_external_user("idp#4", &long, 40);
+        assert_eq!(first.username, "ä".repeat(MAX_USERNAME_LENGTH / 2));
+        accounts.add_external_user(first).unwrap();
+        for external_id in ["idp#5", "idp#5", "idp#6"] {
+            let user = accounts.new_external_user(external_id, &long, 40);
+            assert!(user.username.len() <= MAX_USERNAME_LENGTH);
+            assert!(accounts.find(&user.username).is_none());
+            accounts.add_external_user(user).unwrap();
+        }
+        assert_eq!(accounts.users().len(), 7);
+
+        assert!(accounts.login(&bob.username, "", 40).is_err());
+        assert!(!verify_login(Some(&bob.password_hash), ""));
+        let (token, _) = accounts.start_login(&bob.id, 40);
+        assert_eq!(accounts.user(&token, 50).unwrap().id, bob.id);
+    }
+}
@@ -0,0 +1,116 @@
+#![warn(missing_docs)]
+
+//! PUImURI trainer for Ohms law, power equation and their combinations
+//!
+//! Adaptive difficulty that follows the recent answers of a learner, so strong learners get harder exercises without hand-tuned settings
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::difficulty::Difficulty;
+
+/// How many of the latest answers the level is judged by
+pub const WINDOW: usize = 10;
+
+/// Answers needed on a level before it can change again
+const MIN_ANSWERS: usize = 5;
+
+/// Share of correct answers in the window that moves the level up
+const RAISE_ACCURACY: f64 = 0.8;
+
+/// Share of correct answers in the window at or below which the level moves down
+const LOWER_ACCURACY: f64 = 0.5;
+
+/// Difficulty level of a learner together with the answers given on it
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct AdaptiveDifficulty {
+    /// Current level
+    pub level: Difficulty,
+    /// Whether the latest answers on the current level were correct, oldest first
+    #[serde(default)]
+    pub recent: VecDeque<bool>,
+}
+
+impl AdaptiveDifficulty {
+    /// Starts on the level
+    pub fn new(level: Difficulty) -> AdaptiveDifficulty {
+        AdaptiveDifficulty {
+            level,
+            recent: VecDeque::with_capacity(WINDOW),
+        }
+    }
+
+    /// Share of correct answers in the window, None before enough answers on the level
+    pub fn accuracy(&self) -> Option<f64> {
+        if self.recent.len() < MIN_ANSWERS {
+            return None;
+        }
+        let correct = self.recent.iter().filter(|correct| **correct).count();
+        Some(correct as f64 / self.recent.len() as f64)
+    }
+
+    /// Records an answer and changes the level if the window calls for it, returns the level for the next exercise
+    pub fn record(&mut self, correct: bool) -> Difficulty {
+        self.recent.push_back(correct);
+        if self.recent.len() > WINDOW {
+            self.recent.pop_front();
+        }
+        let level = match self.accuracy() {
+            Some(accuracy) if accuracy >= RAISE_ACCURACY => self.level.harder(),
+            Some(accuracy) if accuracy <= LOWER_ACCURACY => self.level.easier(),
+            _ => self.level,
+        };
+        if level != self.level {
+            // answers on the previous level say nothing about the new one
+            self.level = level;
+            self.recent.clear();
+        }
+        self.level
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strong_learner_levels_up() {
+        let mut adaptive = AdaptiveDifficulty::new(Difficulty::new(3).unwrap());
+        for _ in 0..MIN_ANSWERS - 1 {
+            assert_eq!(adaptive.record(true).level(), 3);
+        }
+        assert_eq!(adaptive.record(true).level(), 4);
+        assert!(adaptive.recent.is_empty());
+
+        for _ in 0..100 {
+            adaptive.record(true);
+        }
+        assert_eq!(adaptive.level, Difficulty::MAX);
+    }
+
+    #[test]
+    fn test_struggling_learner_levels_down() {
+        let mut adaptive = AdaptiveDifficulty::new(Difficulty::new(3).unwrap());
+        for correct in [true, false, true, false, false] {
+            adaptive.record(correct);
+        }
+        assert_eq!(adaptive.level.level(), 2);
+        for _ in 0..100 {
+            adaptive.record(false);
+        }
+        assert_eq!(adaptive.level, Difficulty::MIN);
+    }
+
+    #[test]
+    fn test_mixed_answers_keep_level() {
+        let mut adaptive = AdaptiveDifficulty::new(Difficulty::new(5).unwrap());
+        // two out of three correct stays between the thresholds
+        for _ in 0..20 {
+            for correct in [true, false, true] {
+                adaptive.record(correct);
+            }
+        }
+        assert_eq!(adaptive.level.level(), 5);
+        assert_eq!(adaptive.recent.len(), WINDOW);
+    }
+}
@@ -0,0 +1,307 @@
+#![warn(missing_docs)]
+
+//! PUImURI trainer for Ohms law, power equation and their combinations
+//!
+//! Parser for answers typed by the user, e.g. "2.5k", "2,5 kΩ", "230V", "0.23 kV", "1/3" or "4.7e-3"
+
+use eyre::{Context, Result};
+use std::str::FromStr;
+
+use crate::equations::EquationUnit;
+use crate::units::SiPrefix;
+use crate::TrainerError;
+
+/// Answer typed by the user split into its numeric value and the optional prefix and unit
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ParsedAnswer {
+    /// Numeric value as written, without the prefix applied
+    pub value: f64,
+    /// SI prefix if the user wrote one
+    pub prefix: Option<SiPrefix>,
+    /// Unit if the user wrote one
+    pub unit: Option<EquationUnit>,
+}
+
+impl ParsedAnswer {
+    /// Parses the answer from a string
+    pub fn parse(input: &str) -> Result<ParsedAnswer> {
+        // whitespace is only used for grouping digits or separating the unit, so it can be dropped altogether
+        let normalized: String = input
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| if c == '−' { '-' } else { c })
+            .collect();
+        if normalized.is_empty() {
+            return Err(TrainerError::InvalidAnswer(input.to_string()))
+                .with_context(|| "Answer is empty".to_string());
+        }
+
+        let split = number_length(&normalized);
+        let (number, suffix) = normalized.split_at(split);
+        let value = parse_number(number)
+            .ok_or_else(|| TrainerError::InvalidAnswer(input.to_string()))
+            .with_context(|| format!("Unable to read a number from \"{}\"", number))?;
+        let (prefix, unit) = parse_suffix(suffix)
+            .ok_or_else(|| TrainerError::InvalidAnswer(input.to_string()))
+            .with_context(|| format!("Unknown unit \"{}\"", suffix))?;
+
+        Ok(ParsedAnswer {
+            value,
+            prefix,
+            unit,
+        })
+    }
+
+    /// Returns the answer in base units, a bare number is read in the default prefix while "230 V" is in base units
+    pub fn to_base(&self, default_prefix: SiPrefix) -> f64 {
+        let prefix = match (self.prefix, self.unit) {
+            (Some(prefix), _) => prefix,
+            (None, Some(_)) => SiPrefix::Base,
+            (None, None) => default_prefix,
+        };
+        prefix.to_base(self.value)
+    }
+
+    /// Returns the answer converted into the requested prefix
+    pub fn in_prefix(&self, requested: SiPrefix) -> f64 {
+        requested.from_base(self.to_base(requested))
+    }
+}
+
+impl From<f64> for ParsedAnswer {
+    fn from(value: f64) -> Self {
+        ParsedAnswer {
+            value,
+            prefix: None,
+            unit: None,
+        }
+    }
+}
+
+impl FromStr for ParsedAnswer {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        ParsedAnswer::parse(s)
+    }
+}
+
+/// Returns how many bytes from the start of the input belong to the number
+fn number_length(input: &str) -> usize {
+    let chars: Vec<(usize, char)> = input.char_indices().collect();
+    let mut seen_digit = false;
+    for (position, &(index, c)) in chars.iter().enumerate() {
+        let accepted = match c {
+            '0'..='9' => {
+                seen_digit = true;
+                true
+            }
+            '.' | ',' | '/' => true,
+            '+' | '-' => position == 0 || matches!(chars[position - 1].1, 'e' | 'E' | '/'),
+            'e' | 'E' => {
+                // only an exponent if digits follow, otherwise it is not part of the number
+                let next = chars.get(position + 1).map(|(_, c)| *c);
+                let after = chars.get(position + 2).map(|(_, c)| *c);
+                seen_digit
+                    && match next {
+                        Some('0'..='9') => true,
+                        Some('+') | Some('-') => matches!(after, Some('0'..='9')),
+                        _ => false,
+                    }
+            }
+            _ => false,
+        };
+        if !accepted {
+            return index;
+        }
+    }
+    input.len()
+}
+
+/// Parses a decimal number or a fraction written with either decimal point or comma
+fn parse_number(input: &str) -> Option<f64> {
+    let value = match input.split_once('/') {
+        Some((numerator, denominator)) => {
+            let denominator = parse_decimal(denominator)?;
+            if denominator == 0.0 {
+                return None;
+            }
+            parse_decimal(numerator)? / denominator
+        }
+        None => parse_decimal(input)?,
+    };
+    value.is_finite().then_some(value)
+}
+
+/// Parses a decimal number, the last separator is the decimal separator and any others group thousands
+fn parse_decimal(input: &str) -> Option<f64> {
+    let commas = input.matches(',').count();
+    let points = input.matches('.').count();
+    let decimal_separator = match (input.rfind(','), input.rfind('.')) {
+        (Some(comma), Some(point)) => Some(if comma > point { ',' } else { '.' }),
+        (Some(_), None) if commas == 1 => Some(','),
+        (None, Some(_)) if points == 1 => Some('.'),
+        _ => None,
+    };
+
+    let normalized: String = match decimal_separator {
+        Some(separator) => {
+            let (whole, fraction) = input.rsplit_once(separator)?;
+            format!("{}.{}", ungroup(whole)?, fraction)
+        }
+        None => ungroup(input)?,
+    };
+    if normalized.is_empty() || normalized.starts_with(['e', 'E']) {
+        return None;
+    }
+    normalized.parse::<f64>().ok()
+}
+
+/// Removes the thousands separators of the whole part, None unless every group after the first has three digits
+fn ungroup(whole: &str) -> Option<String> {
+    let digits = whole.trim_start_matches(['+', '-']);
+    let mut separators = digits.chars().filter(|c| matches!(c, ',' | '.'));
+    let Some(separator) = separators.next() else {
+        return Some(whole.to_string());
+    };
+    if separators.any(|other| other != separator) {
+        return None;
+    }
+    let mut groups = digits.split(separator);
+    let first = groups.next()?;
+    let is_digits = |group: &str| group.chars().all(|c| c.is_ascii_digit());
+    if first.is_empty() || first.len() > 3 || !is_digits(first) {
+        return None;
+    }
+    if !groups.all(|group| group.len() == 3 && is_digits(group)) {
+        return None;
+    }
+    Some(whole.replace(separator, ""))
+}
+
+/// Parses the optional prefix and unit that follow the number
+fn parse_suffix(input: &str) -> Option<(Option<SiPrefix>, Option<EquationUnit>)> {
+    const UNITS: [(&str, EquationUnit); 7] = [
+        ("ohms", EquationUnit::Ohm),
+        ("ohm", EquationUnit::Ohm),
+        ("Ω", EquationUnit::Ohm),
+        ("Ω", EquationUnit::Ohm),
+        ("V", EquationUnit::Volt),
+        ("A", EquationUnit::Ampere),
+        ("W", EquationUnit::Watt),
+    ];
+
+    for (symbol, unit) in UNITS {
+        let Some(split) = input.len().checked_sub(symbol.len()) else {
+            continue;
+        };
+        if !input.is_char_boundary(split) || !input[split..].eq_ignore_ascii_case(symbol) {
+            continue;
+        }
+        return Some((parse_prefix(&input[..split])?, Some(unit)));
+    }
+    Some((parse_prefix(input)?, None))
+}
+
+/// Parses the SI prefix symbol, returns None if the symbol is unknown
+fn parse_prefix(input: &str) -> Option<Option<SiPrefix>> {
+    match input {
+        "" => Some(None),
+        "M" => Some(Some(SiPrefix::Mega)),
+        "k" | "K" => Some(Some(SiPrefix::Kilo)),
+        "m" => Some(Some(SiPrefix::Milli)),
+        "µ" | "μ" | "u" => Some(Some(SiPrefix::Micro)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    fn parse(input: &str) -> ParsedAnswer {
+        ParsedAnswer::parse(input).unwrap()
+    }
+
+    #[test]
+    fn test_parse_plain_numbers() {
+        assert_eq!(parse("12").value, 12.0);
+        assert_eq!(parse("2.5").value, 2.5);
+        assert_eq!(parse("2,5").value, 2.5);
+        assert_eq!(parse(" -0,25 ").value, -0.25);
+        assert_eq!(parse("1 200").value, 1200.0);
+        assert_eq!(parse("1 200,5").value, 1200.5);
+        assert_eq!(parse("1,200.5").value, 1200.5);
+        assert_eq!(parse("1.200,5").value, 1200.5);
+        assert_eq!(parse("1.234.567").value, 1234567.0);
+        assert_eq!(parse("-1,234,567.5").value, -1234567.5);
+        assert_eq!(parse("12").prefix, None);
+        assert_eq!(parse("12").unit, None);
+    }
+
+    #[test]
+    fn test_parse_scientific_and_fractions() {
+        assert_relative_eq!(parse("4.7e-3").value, 0.0047);
+        assert_relative_eq!(parse("4,7E3").value, 4700.0);
+        assert_relative_eq!(parse("1/3").value, 1.0 / 3.0);
+        assert_relative_eq!(parse("2,5/5").value, 0.5);
+    }
+
+    #[test]
+    fn test_parse_prefixes_and_units() {
+        let answer = parse("2.5k");
+        assert_eq!(answer.prefix, Some(SiPrefix::Kilo));
+        assert_eq!(answer.unit, None);
+        assert_eq!(answer.to_base(SiPrefix::Base), 2500.0);
+
+        let answer = parse("2,5 kΩ");
+        assert_eq!(answer.prefix, Some(SiPrefix::Kilo));
+        assert_eq!(answer.unit, Some(EquationUnit::Ohm));
+
+        let answer = parse("230V");
+        assert_eq!(answer.prefix, None);
+        assert_eq!(answer.unit, Some(EquationUnit::Volt));
+        assert_eq!(answer.value, 230.0);
+
+        let answer = parse("0.23 kV");
+        assert_eq!(answer.prefix, Some(SiPrefix::Kilo));
+        assert_eq!(answer.unit, Some(EquationUnit::Volt));
+        assert_relative_eq!(answer.to_base(SiPrefix::Base), 230.0);
+
+        assert_eq!(parse("15 mA").prefix, Some(SiPrefix::Milli));
+        assert_eq!(parse("15 µA").prefix, Some(SiPrefix::Micro));
+        assert_eq!(parse("2 MW").prefix, Some(SiPrefix::Mega));
+        assert_eq!(parse("4.7 kohm").unit, Some(EquationUnit::Ohm));
+        assert_eq!(parse("47 ohms").prefix, None);
+        assert_eq!(parse("2 kw").unit, Some(EquationUnit::Watt));
+    }
+
+    #[test]
+    fn test_answer_in_requested_prefix() {
+        assert_relative_eq!(parse("0.015 A").in_prefix(SiPrefix::Milli), 15.0);
+        assert_relative_eq!(parse("15").in_prefix(SiPrefix::Milli), 15.0);
+        assert_relative_eq!(parse("15").to_base(SiPrefix::Milli), 0.015);
+    }
+
+    #[test]
+    fn test_parse_invalid_answers() {
+        assert!(ParsedAnswer::parse("").is_err());
+        assert!(ParsedAnswer::parse("abc").is_err());
+        assert!(ParsedAnswer::parse("12 apples").is_err());
+        assert!(ParsedAnswer::parse("1/0").is_err());
+        assert!(ParsedAnswer::parse("kV").is_err());
+        // separators must group thousands
+        for malformed in [
+            "1.2.3",
+            "1,2,3",
+            "12.34.567",
+            "1.234,5.6",
+            "1,23.4",
+            "1,2345.6",
+        ] {
+            assert!(ParsedAnswer::parse(malformed).is_err(), "{}", malformed);
+        }
+        assert!("2.5 kV".parse::<ParsedAnswer>().is_ok());
+    }
+}
@@ -0,0 +1,390 @@
+//! Local user accounts, logins kept in a cookie and the middleware that limits routes to roles
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use eyre::Result;
+use puimuri_trainer::accounts::{hash_password, verify_login, Accounts, Role, User, LOGIN_TTL};
+use puimuri_trainer::storage::Storage;
+use puimuri_trainer::TrainerError;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+use super::oidc::Identity;
+use super::session::now;
+use super::{blocking, ApiError, Json, Path};
+use crate::AppState;
+
+/// Cookie holding the login token
+pub const COOKIE: &str = "puimuri_session";
+
+/// Accounts kept in memory, shared by all request handlers
+///
+/// With a database every change is written through to it and the accounts are loaded from it on start. Hashing and
+/// database writes happen on blocking threads after the lock has been released.
+#[derive(Clone, Default)]
+pub struct AccountStore {
+    accounts: Arc<Mutex<Accounts>>,
+    storage: Option<Arc<Storage>>,
+}
+
+impl AccountStore {
+    /// Creates the store with the users and logins of the database if there is one
+    pub fn load(storage: Option<Arc<Storage>>) -> Result<AccountStore> {
+        let mut accounts = Accounts::default();
+        if let Some(storage) = &storage {
+            for user in storage.load_users()? {
+                accounts.insert_user(user);
+            }
+            for login in storage.load_logins(now())? {
+                accounts.insert_login(login);
+            }
+        }
+        Ok(AccountStore {
+            accounts: Arc::new(Mutex::new(accounts)),
+            storage,
+        })
+    }
+
+    /// Creates an account, the password is hashed on a blocking thread without holding the lock
+    pub async fn register(
+        &self,
+        username: &str,
+        password: &str,
+        role: Role,
+    ) -> Result<User, ApiError> {
+        self.accounts
+            .lock()
+            .unwrap()
+            .check_new_user(username, password)?;
+        let password = password.to_string();
+        let password_hash = blocking(move || Ok(hash_password(&password)?)).await?;
+        let user = self
+            .accounts
+            .lock()
+            .unwrap()
+            .add_user(username, password_hash, role, now())?
+            .clone();
+        let saved = user.clone();
+        self.write(move |storage| storage.save_user(&saved)).await?;
+        Ok(user)
+    }
+
+    /// Creates the account unless a user with the name exists, e.g. the first admin
+    ///
+    /// Fails if the existing user has a different role, so that a missing admin is noticed at startup.
+    pub async fn ensure(&self, username: &str, password: &str, role: Role) -> Result<(), ApiError> {
+        let existing = self.accounts.lock().unwrap().find(username).cloned();
+        match existing {
+            Some(user) if user.role == role => Ok(()),
+            Some(user) => Err(TrainerError::InvalidAccount(format!(
+                "User \"{}\" exists with the role {} instead of {}",
+                user.username, user.role, role
+            ))
+            .into()),
+            None => self.register(username, password, role).await.map(|_| ()),
+        }
+    }
+
+    /// Checks the password and returns the token of a new login together with the user
+    ///
+    /// Unknown usernames are checked against a dummy hash, so the time taken does not tell which users exist.
+    pub async fn login(&self, username: &str, password: &str) -> Result<(String, User), ApiError> {
+        let user = self.accounts.lock().unwrap().find(username).cloned();
+        let (hash, password) = (
+            user.as_ref().map(|user| user.password_hash.clone()),
+            password.to_string(),
+        );
+        if !blocking(move || Ok(verify_login(hash.as_deref(), &password))).await? {
+            return Err(TrainerError::LoginFailed.into());
+        }
+        let user = user.unwrap();
+        let (token, login) = self.accounts.lock().unwrap().start_login(&user.id, now());
+        self.write(move |storage| storage.save_login(&login))
+            .await?;
+        Ok((token, user))
+    }
+
+    /// Logs in a user identified by a provider, creating their account on their first login
+    pub async fn external_login(&self, identity: &Identity) -> Result<(String, User), ApiError> {
+        let existing = self
+            .accounts
+            .lock()
+            .unwrap()
+            .external_user(&identity.external_id)
+            .cloned();
+        let user = match existing {
+            Some(user) => user,
+            None => {
+                let user = self.accounts.lock().unwrap().new_external_user(
+                    &identity.external_id,
+                    &identity.username,
+                    now(),
+                );
+                // saved before it is added, so that the logins of a user the database refused can not fail
+                let saved = user.clone();
+                self.write(move |storage| storage.save_user(&saved)).await?;
+                self.accounts
+                    .lock()
+                    .unwrap()
+                    .add_external_user(user)?
+                    .clone()
+            }
+        };
+        let (token, login) = self.accounts.lock().unwrap().start_login(&user.id, now());
+        self.write(move |storage| storage.save_login(&login))
+            .await?;
+        Ok((token, user))
+    }
+
+    /// Ends the login of the token
+    pub async fn logout(&self, token: &str) -> Result<(), ApiError> {
+        let login = self.accounts.lock().unwrap().logout(token);
+        if let Some(login) = login {
+            self.write(move |storage| storage.delete_login(&login.token_hash, now()))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// User logged in with the token
+    pub fn user(&self, token: &str) -> Option<User> {
+        self.accounts.lock().unwrap().user(token, now()).cloned()
+    }
+
+    /// Every user in the order they were created
+    pub fn users(&self) -> Vec<User> {
+        self.accounts.lock().unwrap().users().to_vec()
+    }
+
+    /// Changes the role of the user
+    pub async fn set_role(&self, username: &str, role: Role) -> Result<User, ApiError> {
+        let user = self
+            .accounts
+            .lock()
+            .unwrap()
+            .set_role(username, role)?
+            .clone();
+        let saved = user.clone();
+        self.write(move |storage| storage.save_user(&saved)).await?;
+        Ok(user)
+    }
+
+    /// Writes to the database on a blocking thread, after the lock of the accounts has been released
+    async fn write(
+        &self,
+        f: impl FnOnce(&Storage) -> Result<()> + Send + 'static,
+    ) -> Result<(), ApiError> {
+        match self.storage.clone() {
+            Some(storage) => blocking(move || Ok(f(&storage)?)).await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Whose classes and exams a teacher request may see
+#[derive(Clone, Debug, PartialEq)]
+pub enum Owner {
+    /// Admins and requests with the teacher key see everything
+    Anyone,
+    /// Teachers see what they created, identified by their user id
+    User(String),
+}
+
+impl Owner {
+    /// Rejects access to a class or exam created by someone else
+    pub fn check(&self, owner_id: Option<&str>) -> Result<(), ApiError> {
+        match self {
+            Owner::Anyone => Ok(()),
+            Owner::User(id) if owner_id == Some(id.as_str()) => Ok(()),
+            Owner::User(_) => Err(ApiError::new(
+                StatusCode::FORBIDDEN,
+                "Forbidden",
+                "Only the teacher who created it may do this",
+            )),
+        }
+    }
+}
+
+impl AppState {
+    /// User logged in with the cookie of the request
+    pub fn user(&self, headers: &HeaderMap) -> Option<User> {
+        let jar = CookieJar::from_headers(headers);
+        self.accounts.user(jar.get(COOKIE)?.value())
+    }
+
+    /// Rejects requests not made by a user with the role, the teacher key counts as a teacher
+    pub fn require_role(&self, headers: &HeaderMap, role: Role) -> Result<(), ApiError> {
+        if role == Role::Teacher && self.has_teacher_key(headers) {
+            return Ok(());
+        }
+        if self.require_user(headers)?.role.allows(role) {
+            return Ok(());
+        }
+        Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "Forbidden",
+            format!("Only users with the role {} may do this", role),
+        ))
+    }
+
+    /// Logged in user, rejects requests without a valid login cookie
+    pub fn require_user(&self, headers: &HeaderMap) -> Result<User, ApiError> {
+        self.user(headers)
+            .ok_or_else(|| ApiError::new(StatusCode::UNAUTHORIZED, "NotLoggedIn", "Log in first"))
+    }
+
+    /// Whose classes and exams the request may see, rejects requests without a login or the teacher key
+    pub fn owner(&self, headers: &HeaderMap) -> Result<Owner, ApiError> {
+        if self.has_teacher_key(headers) {
+            return Ok(Owner::Anyone);
+        }
+        let user = self.require_user(headers)?;
+        Ok(match user.role {
+            Role::Admin => Owner::Anyone,
+            _ => Owner::User(user.id),
+        })
+    }
+
+    /// Cookie carrying the login token to the browser
+    pub fn login_cookie(&self, token: String) -> Cookie<'static> {
+        Cookie::build((COOKIE, token))
+            .path("/")
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .secure(self.secure_cookies)
+            .max_age(time::Duration::seconds(LOGIN_TTL as i64))
+            .build()
+    }
+}
+
+/// Lets only teachers, admins and requests with the teacher key through
+pub async fn teachers_only(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    state.require_role(request.headers(), Role::Teacher)?;
+    Ok(next.run(request).await)
+}
+
+/// Lets only admins through
+pub async fn admins_only(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    state.require_role(request.headers(), Role::Admin)?;
+    Ok(next.run(request).await)
+}
+
+/// Username and password
+#[derive(Deserialize)]
+pub struct Credentials {
+    username: String,
+    password: String,
+}
+
+/// New account created by an admin
+#[derive(Deserialize)]
+pub struct NewUserRequest {
+    #[serde(flatten)]
+    credentials: Credentials,
+    #[serde(default)]
+    role: Role,
+}
+
+/// New role of a user
+#[derive(Deserialize)]
+pub struct RoleRequest {
+    role: Role,
+}
+
+/// Every user, for admins
+#[derive(Serialize)]
+pub struct UsersResponse {
+    users: Vec<User>,
+}
+
+/// Creates a student account
+pub async fn register(
+    State(state): State<AppState>,
+    Json(credentials): Json<Credentials>,
+) -> Result<(StatusCode, Json<User>), ApiError> {
+    let user = state
+        .accounts
+        .register(&credentials.username, &credentials.password, Role::Student)
+        .await?;
+    Ok((StatusCode::CREATED, Json(user)))
+}
+
+/// Logs the user in and sets the login cookie
+pub async fn login(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(credentials): Json<Credentials>,
+) -> Result<(StatusCode, CookieJar, Json<User>), ApiError> {
+    let (token, user) = state
+        .accounts
+        .login(&credentials.username, &credentials.password)
+        .await?;
+    let jar = jar.add(state.login_cookie(token));
+    Ok((StatusCode::OK, jar, Json(user)))
+}
+
+/// Ends the login and removes the cookie
+pub async fn logout(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<(StatusCode, CookieJar), ApiError> {
+    if let Some(cookie) = jar.get(COOKIE) {
+        state.accounts.logout(cookie.value()).await?;
+    }
+    let jar = jar.remove(Cookie::build(COOKIE).path("/"));
+    Ok((StatusCode::NO_CONTENT, jar))
+}
+
+/// Logged in user
+pub async fn me(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<User>), ApiError> {
+    let user = state.require_user(&headers)?;
+    Ok((StatusCode::OK, Json(user)))
+}
+
+/// Lists every user, only for admins
+pub async fn users(State(state): State<AppState>) -> (StatusCode, Json<UsersResponse>) {
+    let users = state.accounts.users();
+    (StatusCode::OK, Json(UsersResponse { users }))
+}
+
+/// Creates an account with any role, only for admins
+pub async fn create_user(
+    State(state): State<AppState>,
+    Json(request): Json<NewUserRequest>,
+) -> Result<(StatusCode, Json<User>), ApiError> {
+    let user = state
+        .accounts
+        .register(
+            &request.credentials.username,
+            &request.credentials.password,
+            request.role,
+        )
+        .await?;
+    Ok((StatusCode::CREATED, Json(user)))
+}
+
+/// Changes the role of a user, only for admins
+pub async fn set_role(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+    Json(request): Json<RoleRequest>,
+) -> Result<(StatusCode, Json<User>), ApiError> {
+    let user = state.accounts.set_role(&username, request.role).await?;
+    Ok((StatusCode::OK, Json(user)))
+}
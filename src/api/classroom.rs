@@ -0,0 +1,396 @@
+//! Classes with join codes, assignments and the results of every student for teachers
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+};
+use puimuri_trainer::classroom::{
+    join_code, Assignment, AssignmentSpec, AssignmentSubmission, Classroom, StudentResults,
+};
+use puimuri_trainer::equations::EquationExercise;
+use puimuri_trainer::exam::{answered, ExamAnswer};
+use puimuri_trainer::storage::Storage;
+use puimuri_trainer::TrainerError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use super::session::now;
+use super::store::{Record, Store};
+use super::{blocking, ApiError, Json, Path};
+use crate::AppState;
+
+/// How many classes are kept in memory
+pub const MAX_CLASSES: usize = 1_000;
+
+impl Record for Classroom {
+    const NAME: &'static str = "classes";
+
+    fn unknown(id: &str) -> TrainerError {
+        TrainerError::UnknownClass(id.to_string())
+    }
+
+    fn save(&self, storage: &Storage) -> eyre::Result<()> {
+        storage.save_class(self)
+    }
+
+    fn load(storage: &Storage, id: &str) -> eyre::Result<Option<Self>> {
+        storage.load_class(id)
+    }
+}
+
+/// Classes kept in memory, shared by all request handlers
+///
+/// With a database every change is written through to it, and classes not in memory are loaded from it.
+#[derive(Clone)]
+pub struct ClassStore {
+    classes: Store<Classroom>,
+    /// Identifiers of the classes by their join codes, only needed without a database
+    codes: Arc<Mutex<HashMap<String, String>>>,
+    storage: Option<Arc<Storage>>,
+}
+
+impl ClassStore {
+    /// Creates an empty store, optionally backed by a database
+    pub fn new(storage: Option<Arc<Storage>>) -> ClassStore {
+        ClassStore {
+            classes: Store::new(storage.clone(), MAX_CLASSES),
+            codes: Arc::default(),
+            storage,
+        }
+    }
+
+    /// Adds a new class, drawing new join codes for it until it has one no other class has
+    pub async fn insert(&self, mut class: Classroom) -> Result<(), ApiError> {
+        let Some(storage) = self.storage.clone() else {
+            {
+                let mut codes = self.codes.lock().unwrap();
+                while codes.contains_key(&class.join_code) {
+                    class.join_code = join_code();
+                }
+                codes.insert(class.join_code.clone(), class.id.clone());
+            }
+            let code = class.join_code.clone();
+            let inserted = self.classes.insert(class.id.clone(), class).await;
+            if inserted.is_err() {
+                self.codes.lock().unwrap().remove(&code);
+            }
+            return inserted;
+        };
+        loop {
+            let (storage, code) = (storage.clone(), class.join_code.clone());
+            if blocking(move || Ok(storage.class_id(&code)?))
+                .await?
+                .is_none()
+            {
+                break;
+            }
+            class.join_code = join_code();
+        }
+        self.classes.insert(class.id.clone(), class).await
+    }
+
+    /// Runs the function on the class, failing if the class does not exist
+    pub async fn update<T: Send + 'static>(
+        &self,
+        id: &str,
+        f: impl FnOnce(&mut Classroom) -> Result<T, ApiError> + Send + 'static,
+    ) -> Result<T, ApiError> {
+        self.classes.update(id, f).await
+    }
+
+    /// Runs the function on the class with the join code
+    pub async fn update_by_code<T: Send + 'static>(
+        &self,
+        code: &str,
+        f: impl FnOnce(&mut Classroom) -> Result<T, ApiError> + Send + 'static,
+    ) -> Result<T, ApiError> {
+        let join_code = code.trim().to_ascii_uppercase();
+        let id = match self.storage.clone() {
+            Some(storage) => blocking(move || Ok(storage.class_id(&join_code)?)).await?,
+            None => self.codes.lock().unwrap().get(&join_code).cloned(),
+        };
+        let id = id.ok_or_else(|| TrainerError::InvalidJoinCode(code.to_string()))?;
+        self.update(&id, f).await
+    }
+}
+
+/// New class
+#[derive(Deserialize)]
+pub struct CreateRequest {
+    name: String,
+}
+
+/// Identifier and join code of a class
+#[derive(Serialize)]
+pub struct ClassResponse {
+    id: String,
+    name: String,
+    join_code: String,
+}
+
+/// Assignment without its exercises
+#[derive(Serialize)]
+pub struct AssignmentSummary {
+    id: String,
+    title: String,
+    due_at: u64,
+    count: usize,
+}
+
+impl From<&Assignment> for AssignmentSummary {
+    fn from(assignment: &Assignment) -> Self {
+        AssignmentSummary {
+            id: assignment.id.clone(),
+            title: assignment.spec.title.clone(),
+            due_at: assignment.spec.due_at,
+            count: assignment.exercises.len(),
+        }
+    }
+}
+
+/// Logged in student joining a class with its code
+#[derive(Deserialize)]
+pub struct JoinRequest {
+    code: String,
+}
+
+/// Class as a student sees it
+#[derive(Serialize)]
+pub struct JoinResponse {
+    id: String,
+    name: String,
+    assignments: Vec<AssignmentSummary>,
+}
+
+/// Exercises of an assignment, with the graded answers of the student once it is due
+#[derive(Serialize)]
+pub struct AssignmentResponse {
+    #[serde(flatten)]
+    summary: AssignmentSummary,
+    exercises: Vec<EquationExercise>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    submission: Option<AssignmentSubmission>,
+}
+
+/// Receipt of a submission, which answers were right is only told once the assignment is due
+#[derive(Serialize)]
+pub struct SubmitResponse {
+    student: String,
+    submitted_at: u64,
+    answered: usize,
+    late: bool,
+    results_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    submission: Option<AssignmentSubmission>,
+}
+
+/// Submission of the student to the assignment, None before the due date so that answers can not be tried out
+fn graded(assignment: &Assignment, student: &str, now: u64) -> Option<AssignmentSubmission> {
+    (now > assignment.spec.due_at)
+        .then(|| assignment.submission(student).cloned())
+        .flatten()
+}
+
+/// Answers of the logged in student to an assignment
+#[derive(Deserialize)]
+pub struct SubmitRequest {
+    answers: Vec<ExamAnswer>,
+}
+
+/// Results of every student of the class
+#[derive(Serialize)]
+pub struct ResultsResponse {
+    assignments: Vec<AssignmentSummary>,
+    students: Vec<StudentResults>,
+}
+
+/// Creates a class, only for teachers
+pub async fn create(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<CreateRequest>,
+) -> Result<(StatusCode, Json<ClassResponse>), ApiError> {
+    let mut class = Classroom::new(&request.name, now());
+    class.owner_id = state.user(&headers).map(|user| user.id);
+    let response = ClassResponse {
+        id: class.id.clone(),
+        name: class.name.clone(),
+        join_code: class.join_code.clone(),
+    };
+    state.classes.insert(class).await?;
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// Shows the whole class to the teacher who created it
+pub async fn show(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<Classroom>), ApiError> {
+    let owner = state.owner(&headers)?;
+    let class = state
+        .classes
+        .update(&id, move |class| {
+            owner.check(class.owner_id.as_deref())?;
+            Ok(class.clone())
+        })
+        .await?;
+    Ok((StatusCode::OK, Json(class)))
+}
+
+/// Enrols the logged in student in the class with the join code
+pub async fn join(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<JoinRequest>,
+) -> Result<(StatusCode, Json<JoinResponse>), ApiError> {
+    let student = state.require_user(&headers)?.username;
+    let response = state
+        .classes
+        .update_by_code(&request.code, move |class| {
+            class.join(&student, now())?;
+            Ok(JoinResponse {
+                id: class.id.clone(),
+                name: class.name.clone(),
+                assignments: class
+                    .assignments
+                    .iter()
+                    .map(AssignmentSummary::from)
+                    .collect(),
+            })
+        })
+        .await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Creates an assignment, only for the teacher of the class
+pub async fn assign(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(spec): Json<AssignmentSpec>,
+) -> Result<(StatusCode, Json<AssignmentSummary>), ApiError> {
+    let owner = state.owner(&headers)?;
+    let assignment = blocking(move || Ok(Assignment::new(spec, now())?)).await?;
+    let summary = state
+        .classes
+        .update(&id, move |class| {
+            owner.check(class.owner_id.as_deref())?;
+            Ok(AssignmentSummary::from(class.add_assignment(assignment)))
+        })
+        .await?;
+    Ok((StatusCode::CREATED, Json(summary)))
+}
+
+/// Hands out the exercises of an assignment to the logged in student if enrolled
+pub async fn assignment(
+    State(state): State<AppState>,
+    Path((id, assignment_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<AssignmentResponse>), ApiError> {
+    let student = state.require_user(&headers)?.username;
+    let response = state
+        .classes
+        .update(&id, move |class| {
+            class.require_student(&student)?;
+            let assignment = class.assignment(&assignment_id)?;
+            Ok(AssignmentResponse {
+                summary: AssignmentSummary::from(assignment),
+                exercises: assignment.exercises.clone(),
+                submission: graded(assignment, &student, now()),
+            })
+        })
+        .await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Grades the answers of the logged in student to an assignment, until the due date later answers replace earlier ones
+pub async fn submit(
+    State(state): State<AppState>,
+    Path((id, assignment_id)): Path<(String, String)>,
+    headers: HeaderMap,
+    Json(request): Json<SubmitRequest>,
+) -> Result<(StatusCode, Json<SubmitResponse>), ApiError> {
+    let student = state.require_user(&headers)?.username;
+    let (policy, scoring) = (state.grading_policy, state.scoring_model);
+    let response = state
+        .classes
+        .update(&id, move |class| {
+            let now = now();
+            let submission = class
+                .submit(
+                    &assignment_id,
+                    &student,
+                    &request.answers,
+                    policy,
+                    &scoring,
+                    now,
+                )?
+                .clone();
+            let assignment = class.assignment(&assignment_id)?;
+            Ok(SubmitResponse {
+                student: submission.submission.student.clone(),
+                submitted_at: submission.submission.submitted_at,
+                answered: answered(&submission.submission.results),
+                late: submission.late,
+                results_at: assignment.spec.due_at,
+                submission: graded(assignment, &student, now),
+            })
+        })
+        .await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Results of every student in every assignment, only for the teacher of the class
+pub async fn results(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<ResultsResponse>), ApiError> {
+    let owner = state.owner(&headers)?;
+    let response = state
+        .classes
+        .update(&id, move |class| {
+            owner.check(class.owner_id.as_deref())?;
+            Ok(ResultsResponse {
+                assignments: class
+                    .assignments
+                    .iter()
+                    .map(AssignmentSummary::from)
+                    .collect(),
+                students: class.results(),
+            })
+        })
+        .await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_join_codes_are_unique() {
+        for storage in [None, Some(Arc::new(Storage::open_in_memory().unwrap()))] {
+            let classes = ClassStore::new(storage);
+            let first = Classroom::new("Electricians 1", 0);
+            let mut second = Classroom::new("Electricians 2", 0);
+            second.join_code = first.join_code.clone();
+            let (code, id) = (first.join_code.clone(), second.id.clone());
+            classes.insert(first).await.unwrap();
+            classes.insert(second).await.unwrap();
+
+            let second_code = classes
+                .update(&id, |class| Ok(class.join_code.clone()))
+                .await
+                .unwrap();
+            assert_ne!(second_code, code);
+            let joined = classes
+                .update_by_code(&second_code.to_lowercase(), |class| Ok(class.id.clone()))
+                .await
+                .unwrap();
+            assert_eq!(joined, id);
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! Errors returned by the REST interface as JSON bodies with a matching status code
+
+use axum::{
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use puimuri_trainer::TrainerError;
+use serde::Serialize;
+
+/// Error response, serialized as `{"error": "InvalidToken", "message": "..."}`
+#[derive(Debug, Serialize)]
+pub struct ApiError {
+    #[serde(skip)]
+    status: StatusCode,
+    error: &'static str,
+    message: String,
+}
+
+impl ApiError {
+    /// Creates an error response with a machine readable kind and a human readable message
+    pub fn new(status: StatusCode, error: &'static str, message: impl Into<String>) -> ApiError {
+        ApiError {
+            status,
+            error,
+            message: message.into(),
+        }
+    }
+}
+
+/// Status code and kind of the error as seen by the client
+fn classify(error: &TrainerError) -> (StatusCode, &'static str) {
+    match error {
+        TrainerError::MinLargerThanMax => (StatusCode::BAD_REQUEST, "MinLargerThanMax"),
+        TrainerError::UnsatisfiableRange(_) => (StatusCode::BAD_REQUEST, "UnsatisfiableRange"),
+        TrainerError::MissingVariable(_) => (StatusCode::UNPROCESSABLE_ENTITY, "MissingVariable"),
+        TrainerError::InvalidMissingVariable(_, _) => {
+            (StatusCode::UNPROCESSABLE_ENTITY, "InvalidMissingVariable")
+        }
+        TrainerError::EquationResolveError(_) => {
+            (StatusCode::UNPROCESSABLE_ENTITY, "EquationResolveError")
+        }
+        TrainerError::InvalidAnswer(_) => (StatusCode::BAD_REQUEST, "InvalidAnswer"),
+        TrainerError::InvalidGradingPolicy(_) => (StatusCode::BAD_REQUEST, "InvalidGradingPolicy"),
+        TrainerError::InvalidDifficulty(_) => (StatusCode::BAD_REQUEST, "InvalidDifficulty"),
+        TrainerError::NoExerciseType => (StatusCode::BAD_REQUEST, "NoExerciseType"),
+        TrainerError::InvalidSetSize(_) => (StatusCode::BAD_REQUEST, "InvalidSetSize"),
+        TrainerError::UnknownSession(_) => (StatusCode::NOT_FOUND, "UnknownSession"),
+        TrainerError::ExerciseNotIssued(_) => (StatusCode::CONFLICT, "ExerciseNotIssued"),
+        TrainerError::InvalidToken => (StatusCode::FORBIDDEN, "InvalidToken"),
+        TrainerError::InvalidTimeLimit(_) => (StatusCode::BAD_REQUEST, "InvalidTimeLimit"),
+        TrainerError::UnknownExam(_) => (StatusCode::NOT_FOUND, "UnknownExam"),
+        TrainerError::ExamClosed(_) => (StatusCode::CONFLICT, "ExamClosed"),
+        TrainerError::ExamNotClosed(_) => (StatusCode::CONFLICT, "ExamNotClosed"),
+        TrainerError::AlreadySubmitted(_) => (StatusCode::CONFLICT, "AlreadySubmitted"),
+        TrainerError::ExamFull(_) => (StatusCode::CONFLICT, "ExamFull"),
+        TrainerError::UnknownExercise(_) => (StatusCode::UNPROCESSABLE_ENTITY, "UnknownExercise"),
+        TrainerError::DuplicateAnswer(_) => (StatusCode::UNPROCESSABLE_ENTITY, "DuplicateAnswer"),
+        TrainerError::UnknownClass(_) => (StatusCode::NOT_FOUND, "UnknownClass"),
+        TrainerError::InvalidJoinCode(_) => (StatusCode::NOT_FOUND, "InvalidJoinCode"),
+        TrainerError::NotEnrolled(_) => (StatusCode::FORBIDDEN, "NotEnrolled"),
+        TrainerError::ClassFull(_) => (StatusCode::CONFLICT, "ClassFull"),
+        TrainerError::UnknownAssignment(_) => (StatusCode::NOT_FOUND, "UnknownAssignment"),
+        TrainerError::SubmittedOnTime(_) => (StatusCode::CONFLICT, "SubmittedOnTime"),
+        TrainerError::InvalidAccount(_) => (StatusCode::BAD_REQUEST, "InvalidAccount"),
+        TrainerError::UsernameTaken(_) => (StatusCode::CONFLICT, "UsernameTaken"),
+        TrainerError::UnknownUser(_) => (StatusCode::NOT_FOUND, "UnknownUser"),
+        TrainerError::LoginFailed => (StatusCode::UNAUTHORIZED, "LoginFailed"),
+    }
+}
+
+impl From<TrainerError> for ApiError {
+    fn from(error: TrainerError) -> Self {
+        let (status, kind) = classify(&error);
+        ApiError::new(status, kind, error.to_string())
+    }
+}
+
+impl From<eyre::Report> for ApiError {
+    fn from(report: eyre::Report) -> Self {
+        match report.downcast_ref::<TrainerError>() {
+            Some(error) => {
+                let (status, kind) = classify(error);
+                ApiError::new(status, kind, format!("{:#}", report))
+            }
+            None => {
+                tracing::error!("{:?}", report);
+                ApiError::new(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "InternalError",
+                    "Internal server error",
+                )
+            }
+        }
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::new(rejection.status(), "InvalidRequest", rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::new(rejection.status(), "InvalidRequest", rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::new(rejection.status(), "InvalidRequest", rejection.body_text())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, axum::Json(&self)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::WrapErr;
+    use puimuri_trainer::equations::{EquationExerciseType, EquationVariable};
+
+    #[test]
+    fn test_trainer_errors_keep_their_kind() {
+        let report = Err::<(), _>(TrainerError::InvalidMissingVariable(
+            EquationExerciseType::OhmsLaw,
+            EquationVariable::Power,
+        ))
+        .wrap_err("Exercise asks for a variable its equation does not solve")
+        .unwrap_err();
+        let error = ApiError::from(report);
+        assert_eq!(error.status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error.error, "InvalidMissingVariable");
+        assert!(error
+            .message
+            .contains("Power can not be asked in a OhmsLaw exercise"));
+
+        let error = ApiError::from(TrainerError::InvalidToken);
+        assert_eq!(error.status, StatusCode::FORBIDDEN);
+        assert_eq!(error.error, "InvalidToken");
+    }
+
+    #[test]
+    fn test_unknown_errors_are_internal() {
+        let error = ApiError::from(eyre::eyre!("database is on fire"));
+        assert_eq!(error.status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(error.message, "Internal server error");
+    }
+}
@@ -0,0 +1,204 @@
+//! Timed exams defined by a teacher, answered once by every student and graded when they close
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+};
+use puimuri_trainer::equations::EquationExercise;
+use puimuri_trainer::exam::{answered, Exam, ExamAnswer, ExamSpec, Submission};
+use puimuri_trainer::storage::Storage;
+use puimuri_trainer::TrainerError;
+use serde::{Deserialize, Serialize};
+
+use super::session::now;
+use super::store::{Record, Store};
+use super::{blocking, ApiError, Json, Path};
+use crate::AppState;
+
+/// How many exams are kept in memory
+pub const MAX_EXAMS: usize = 1_000;
+
+/// Exams kept in memory, shared by all request handlers
+///
+/// With a database every change is written through to it, and exams not in memory are loaded from it.
+pub type ExamStore = Store<Exam>;
+
+impl Record for Exam {
+    const NAME: &'static str = "exams";
+
+    fn unknown(id: &str) -> TrainerError {
+        TrainerError::UnknownExam(id.to_string())
+    }
+
+    fn save(&self, storage: &Storage) -> eyre::Result<()> {
+        storage.save_exam(self)
+    }
+
+    fn load(storage: &Storage, id: &str) -> eyre::Result<Option<Self>> {
+        storage.load_exam(id)
+    }
+}
+
+/// Identifier and schedule of an exam
+#[derive(Serialize)]
+pub struct ExamResponse {
+    id: String,
+    opens_at: u64,
+    closes_at: u64,
+    count: usize,
+}
+
+impl From<&Exam> for ExamResponse {
+    fn from(exam: &Exam) -> Self {
+        ExamResponse {
+            id: exam.id.clone(),
+            opens_at: exam.opens_at,
+            closes_at: exam.closes_at,
+            count: exam.exercises.len(),
+        }
+    }
+}
+
+/// Everything about an exam for the teacher, results and the answer key once it has closed
+#[derive(Serialize)]
+pub struct ExamOverview {
+    #[serde(flatten)]
+    exam: ExamResponse,
+    spec: ExamSpec,
+    submitted: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    submissions: Option<Vec<Submission>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    answer_key: Option<Vec<EquationExercise>>,
+}
+
+/// Exercises of the exam as one student sees them
+#[derive(Serialize)]
+pub struct PaperResponse {
+    id: String,
+    closes_at: u64,
+    exercises: Vec<EquationExercise>,
+}
+
+/// Answers of the logged in student, locked once accepted
+#[derive(Deserialize)]
+pub struct SubmitRequest {
+    answers: Vec<ExamAnswer>,
+}
+
+/// Receipt of a submission, the results are released when the exam closes
+#[derive(Serialize)]
+pub struct SubmitResponse {
+    student: String,
+    submitted_at: u64,
+    answered: usize,
+    results_at: u64,
+}
+
+/// Creates an exam, only for teachers
+pub async fn create(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(spec): Json<ExamSpec>,
+) -> Result<(StatusCode, Json<ExamResponse>), ApiError> {
+    let mut exam = blocking(move || Ok(Exam::new(spec, now())?)).await?;
+    exam.owner_id = state.user(&headers).map(|user| user.id);
+    let response = ExamResponse::from(&exam);
+    state.exams.insert(exam.id.clone(), exam).await?;
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// Shows the exam to the teacher who created it
+pub async fn show(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<ExamOverview>), ApiError> {
+    let owner = state.owner(&headers)?;
+    let overview = state
+        .exams
+        .update(&id, move |exam| {
+            owner.check(exam.owner_id.as_deref())?;
+            let closed = !exam.is_open(now());
+            Ok(ExamOverview {
+                exam: ExamResponse::from(&*exam),
+                spec: exam.spec.clone(),
+                submitted: exam
+                    .submissions
+                    .iter()
+                    .map(|submission| submission.student.clone())
+                    .collect(),
+                submissions: closed.then(|| exam.submissions.clone()),
+                answer_key: closed.then(|| exam.answer_key(now())).transpose()?,
+            })
+        })
+        .await?;
+    Ok((StatusCode::OK, Json(overview)))
+}
+
+/// Hands out the exercises to the logged in student while the exam is open
+pub async fn paper(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<PaperResponse>), ApiError> {
+    let student = state.require_user(&headers)?.username;
+    let response = state
+        .exams
+        .update(&id, move |exam| {
+            Ok(PaperResponse {
+                id: exam.id.clone(),
+                closes_at: exam.closes_at,
+                exercises: exam.paper(&student, now())?,
+            })
+        })
+        .await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Grades and locks the answers of the logged in student without telling how they went
+pub async fn submit(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<SubmitRequest>,
+) -> Result<(StatusCode, Json<SubmitResponse>), ApiError> {
+    let student = state.require_user(&headers)?.username;
+    let (policy, scoring) = (state.grading_policy, state.scoring_model);
+    let response = state
+        .exams
+        .update(&id, move |exam| {
+            let closes_at = exam.closes_at;
+            let submission = exam.submit(&student, &request.answers, policy, &scoring, now())?;
+            Ok(SubmitResponse {
+                student: submission.student.clone(),
+                submitted_at: submission.submitted_at,
+                answered: answered(&submission.results),
+                results_at: closes_at,
+            })
+        })
+        .await?;
+    Ok((StatusCode::ACCEPTED, Json(response)))
+}
+
+/// Results of the logged in student once the exam has closed
+pub async fn results(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<Submission>), ApiError> {
+    let student = state.require_user(&headers)?.username;
+    let submission = state
+        .exams
+        .update(&id, move |exam| {
+            exam.results(&student, now())?.cloned().ok_or_else(|| {
+                ApiError::new(
+                    StatusCode::NOT_FOUND,
+                    "NoSubmission",
+                    format!("\"{}\" did not submit answers", student),
+                )
+            })
+        })
+        .await?;
+    Ok((StatusCode::OK, Json(submission)))
+}
@@ -0,0 +1,24 @@
+//! REST interface building blocks shared by the request handlers and the handlers of larger subsystems
+
+pub mod account;
+pub mod classroom;
+pub mod error;
+pub mod exam;
+pub mod extract;
+pub mod oidc;
+pub mod session;
+pub mod store;
+
+pub use error::ApiError;
+pub use extract::{Json, Path, Query};
+
+/// Runs work that blocks, like generating many exercises, on a thread of its own so that other requests are not held up
+pub async fn blocking<T, F>(work: F) -> Result<T, ApiError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, ApiError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(eyre::Report::from)?
+}
@@ -0,0 +1,488 @@
+//! Single sign-on with an OpenID Connect identity provider, users logging in through it become students
+
+use axum::{extract::State, http::StatusCode, response::Redirect};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use eyre::{eyre, Context, Result};
+use openidconnect::core::{
+    CoreAuthenticationFlow, CoreClient, CoreJsonWebKey, CoreJwsSigningAlgorithm,
+    CoreProviderMetadata,
+};
+use openidconnect::url::Url;
+use openidconnect::{
+    reqwest, AccessTokenHash, AuthorizationCode, ClientId, ClientSecret, CsrfToken,
+    EndpointMaybeSet, EndpointNotSet, EndpointSet, IssuerUrl, JsonWebKey, JwsSigningAlgorithm,
+    Nonce, OAuth2TokenResponse, PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, Scope,
+    TokenResponse,
+};
+use serde::Deserialize;
+use std::env;
+use subtle::ConstantTimeEq;
+
+use super::session::now;
+use super::{ApiError, Query};
+use crate::AppState;
+
+/// Seconds a user has to log in at the identity provider
+const PENDING_TTL: u64 = 10 * 60;
+
+/// Cookie holding the login started in the browser until the provider sends the user back
+pub const COOKIE: &str = "puimuri_oidc";
+
+/// Client of the identity provider as built from its discovery document
+type ProviderClient = CoreClient<
+    EndpointSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointMaybeSet,
+    EndpointMaybeSet,
+>;
+
+/// Identity provider and client registered at it
+#[derive(Clone, Debug)]
+pub struct OidcConfig {
+    /// Issuer URL the discovery document is fetched from, e.g. "https://login.example.com/realms/school"
+    pub issuer: String,
+    /// Client id of the trainer at the provider
+    pub client_id: String,
+    /// Client secret of the trainer at the provider
+    pub client_secret: String,
+    /// Where the provider sends the user back to, the public URL of /api/oidc/callback
+    pub redirect_url: String,
+}
+
+impl OidcConfig {
+    /// Settings from the environment, None unless PUIMURI_OIDC_ISSUER is set
+    pub fn from_env() -> Result<Option<OidcConfig>> {
+        let Ok(issuer) = env::var("PUIMURI_OIDC_ISSUER") else {
+            return Ok(None);
+        };
+        let var = |name: &str| {
+            env::var(name).with_context(|| format!("{} is needed with PUIMURI_OIDC_ISSUER", name))
+        };
+        Ok(Some(OidcConfig {
+            issuer,
+            client_id: var("PUIMURI_OIDC_CLIENT_ID")?,
+            client_secret: var("PUIMURI_OIDC_CLIENT_SECRET")?,
+            redirect_url: var("PUIMURI_OIDC_REDIRECT_URL")?,
+        }))
+    }
+}
+
+/// Login started here and not yet finished at the provider, kept in a cookie of the browser that started it
+///
+/// The callback only accepts the state of the login in the cookie, so nobody can finish their own login in the browser
+/// of someone else.
+pub struct PendingLogin {
+    state: String,
+    pkce_verifier: PkceCodeVerifier,
+    nonce: Nonce,
+    expires_at: u64,
+}
+
+impl PendingLogin {
+    /// Value of the cookie, the random parts are URL safe base64 without dots
+    fn encode(&self) -> String {
+        format!(
+            "{}.{}.{}.{}",
+            self.state,
+            self.pkce_verifier.secret(),
+            self.nonce.secret(),
+            self.expires_at
+        )
+    }
+
+    /// Login from the value of the cookie, None if it is malformed
+    fn decode(value: &str) -> Option<PendingLogin> {
+        let mut parts = value.split('.');
+        let login = PendingLogin {
+            state: parts.next()?.to_string(),
+            pkce_verifier: PkceCodeVerifier::new(parts.next()?.to_string()),
+            nonce: Nonce::new(parts.next()?.to_string()),
+            expires_at: parts.next()?.parse().ok()?,
+        };
+        parts.next().is_none().then_some(login)
+    }
+}
+
+/// User as identified by the provider
+#[derive(Clone, Debug, PartialEq)]
+pub struct Identity {
+    /// Issuer and subject, unique for every user of every provider
+    pub external_id: String,
+    /// Name suggested by the provider, the preferred username, e-mail address or subject
+    pub username: String,
+}
+
+/// Identity provider discovered at startup, shared by all request handlers
+#[derive(Clone)]
+pub struct OidcProvider {
+    issuer: String,
+    client_secret: String,
+    client: ProviderClient,
+    http: reqwest::Client,
+    algorithms: Vec<CoreJwsSigningAlgorithm>,
+}
+
+impl OidcProvider {
+    /// Fetches the discovery document of the provider
+    pub async fn discover(config: OidcConfig) -> Result<OidcProvider> {
+        let http = reqwest::Client::builder()
+            // following redirects would let the provider make the server request any URL
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        let metadata =
+            CoreProviderMetadata::discover_async(IssuerUrl::new(config.issuer.clone())?, &http)
+                .await
+                .with_context(|| format!("Unable to discover provider {}", config.issuer))?;
+        let algorithms = metadata.id_token_signing_alg_values_supported().clone();
+        let client = CoreClient::from_provider_metadata(
+            metadata,
+            ClientId::new(config.client_id),
+            Some(ClientSecret::new(config.client_secret.clone())),
+        )
+        .set_redirect_uri(RedirectUrl::new(config.redirect_url)?);
+        Ok(OidcProvider {
+            issuer: config.issuer,
+            client_secret: config.client_secret,
+            client,
+            http,
+            algorithms,
+        })
+    }
+
+    /// Starts a login, returns the URL of the provider to send the user to and the login to keep in the browser
+    pub fn authorize_url(&self, now: u64) -> (Url, PendingLogin) {
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+        let (url, state, nonce) = self
+            .client
+            .authorize_url(
+                CoreAuthenticationFlow::AuthorizationCode,
+                CsrfToken::new_random,
+                Nonce::new_random,
+            )
+            .add_scope(Scope::new("profile".to_string()))
+            .add_scope(Scope::new("email".to_string()))
+            .set_pkce_challenge(pkce_challenge)
+            .url();
+        let login = PendingLogin {
+            state: state.secret().clone(),
+            pkce_verifier,
+            nonce,
+            expires_at: now.saturating_add(PENDING_TTL),
+        };
+        (url, login)
+    }
+
+    /// Finishes the login started in the browser, exchanging the code for a verified ID token
+    ///
+    /// The state sent back by the provider must be the one of the login started in the browser.
+    pub async fn identify(
+        &self,
+        code: &str,
+        state: &str,
+        login: Option<PendingLogin>,
+        now: u64,
+    ) -> Result<Identity, ApiError> {
+        let login = login
+            .filter(|login| bool::from(login.state.as_bytes().ct_eq(state.as_bytes())))
+            .filter(|login| login.expires_at > now)
+            .ok_or_else(|| {
+                ApiError::new(
+                    StatusCode::UNAUTHORIZED,
+                    "LoginFailed",
+                    "The login was not started in this browser or took too long",
+                )
+            })?;
+        let response = self
+            .client
+            .exchange_code(AuthorizationCode::new(code.to_string()))
+            .map_err(|error| {
+                provider_error(eyre!("The provider has no token endpoint: {}", error))
+            })?
+            .set_pkce_verifier(login.pkce_verifier)
+            .request_async(&self.http)
+            .await
+            .map_err(|error| provider_error(eyre!("Unable to exchange the code: {}", error)))?;
+        let id_token = response
+            .id_token()
+            .ok_or_else(|| provider_error(eyre!("The provider did not return an ID token")))?;
+        let verifier = self
+            .client
+            .id_token_verifier()
+            .set_allowed_algs(self.algorithms.clone());
+        let claims = id_token
+            .claims(&verifier, &login.nonce)
+            .map_err(invalid_token)?;
+        // the access token must be the one the ID token was issued with
+        if let Some(expected) = claims.access_token_hash() {
+            let alg = id_token.signing_alg().map_err(invalid_token)?;
+            // tokens signed with the client secret have no key of the provider
+            let key = match alg.uses_shared_secret() {
+                true => CoreJsonWebKey::new_symmetric(self.client_secret.as_bytes().to_vec()),
+                false => id_token
+                    .signing_key(&verifier)
+                    .map_err(invalid_token)?
+                    .clone(),
+            };
+            let actual = AccessTokenHash::from_token(response.access_token(), alg, &key)
+                .map_err(invalid_token)?;
+            if actual != *expected {
+                return Err(provider_error(eyre!("Invalid access token")));
+            }
+        }
+
+        let subject = claims.subject().as_str();
+        let username = claims
+            .preferred_username()
+            .map(|username| username.as_str())
+            .or_else(|| claims.email().map(|email| email.as_str()))
+            .unwrap_or(subject);
+        Ok(Identity {
+            external_id: format!("{}#{}", self.issuer, subject),
+            username: username.to_string(),
+        })
+    }
+}
+
+/// The provider failed or answered with something that can not be trusted
+fn provider_error(report: eyre::Report) -> ApiError {
+    ApiError::new(
+        StatusCode::BAD_GATEWAY,
+        "ProviderError",
+        format!("{:#}", report),
+    )
+}
+
+/// The ID token can not be trusted
+fn invalid_token(error: impl std::fmt::Display) -> ApiError {
+    provider_error(eyre!("Invalid ID token: {}", error))
+}
+
+impl AppState {
+    /// Provider, failing when single sign-on is not configured
+    fn oidc(&self) -> Result<&OidcProvider, ApiError> {
+        self.oidc.as_ref().ok_or_else(|| {
+            ApiError::new(
+                StatusCode::NOT_FOUND,
+                "NotConfigured",
+                "Single sign-on is not configured",
+            )
+        })
+    }
+}
+
+/// Answer of the provider, a code and the state of the login or an error
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+/// Sends the user to the provider to log in, remembering the login in a short-lived cookie
+pub async fn login(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<(CookieJar, Redirect), ApiError> {
+    let (url, login) = state.oidc()?.authorize_url(now());
+    // the provider sends the user back with a top-level GET, which SameSite=Lax cookies are sent with
+    let cookie = Cookie::build((COOKIE, login.encode()))
+        .path("/api/oidc")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .secure(state.secure_cookies)
+        .max_age(time::Duration::seconds(PENDING_TTL as i64))
+        .build();
+    Ok((jar.add(cookie), Redirect::to(url.as_str())))
+}
+
+/// Logs the user in after the provider sent them back, creating their account on their first login
+pub async fn callback(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Query(query): Query<CallbackQuery>,
+) -> Result<(CookieJar, Redirect), ApiError> {
+    let provider = state.oidc()?;
+    if let Some(error) = query.error {
+        return Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "LoginFailed",
+            format!(
+                "The provider refused the login: {}",
+                query.error_description.unwrap_or(error)
+            ),
+        ));
+    }
+    let (Some(code), Some(login_state)) = (query.code, query.state) else {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "InvalidRequest",
+            "The provider must send a code and the state of the login",
+        ));
+    };
+    let login = jar
+        .get(COOKIE)
+        .and_then(|cookie| PendingLogin::decode(cookie.value()));
+    let identity = provider.identify(&code, &login_state, login, now()).await?;
+    let (token, _) = state.accounts.external_login(&identity).await?;
+    let jar = jar
+        .remove(Cookie::build(COOKIE).path("/api/oidc"))
+        .add(state.login_cookie(token));
+    Ok((jar, Redirect::to("/")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::Form;
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use openidconnect::core::{CoreHmacKey, CoreIdToken, CoreIdTokenClaims};
+    use openidconnect::AccessToken;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    const CLIENT_ID: &str = "puimuri";
+    const CLIENT_SECRET: &str = "a client secret of at least 32 bytes";
+
+    /// Nonces of the logins the mock provider has authorized, by code
+    type Authorized = Arc<Mutex<HashMap<String, String>>>;
+
+    /// Identity provider on a local port, signing ID tokens with the client secret
+    async fn mock_provider(authorized: Authorized) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let discovery = json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{}/authorize", issuer),
+            "token_endpoint": format!("{}/token", issuer),
+            "jwks_uri": format!("{}/jwks", issuer),
+            "response_types_supported": ["code"],
+            "subject_types_supported": ["public"],
+            "id_token_signing_alg_values_supported": ["HS256"],
+        });
+        let token_issuer = issuer.clone();
+        let token = move |Form(form): Form<HashMap<String, String>>| async move {
+            let nonce = authorized.lock().unwrap().remove(&form["code"]).unwrap();
+            let claims: CoreIdTokenClaims = serde_json::from_value(json!({
+                "iss": token_issuer,
+                "aud": [CLIENT_ID],
+                "exp": now() + 300,
+                "iat": now(),
+                "sub": "248289761001",
+                "nonce": nonce,
+                "preferred_username": "bob",
+            }))
+            .unwrap();
+            let access_token = AccessToken::new("access".to_string());
+            let id_token = CoreIdToken::new(
+                claims,
+                &CoreHmacKey::new(CLIENT_SECRET),
+                CoreJwsSigningAlgorithm::HmacSha256,
+                Some(&access_token),
+                None,
+            )
+            .unwrap();
+            Json(json!({
+                "access_token": access_token.secret(),
+                "token_type": "bearer",
+                "id_token": id_token,
+            }))
+        };
+        let app = Router::new()
+            .route(
+                "/.well-known/openid-configuration",
+                get(move || async move { Json(discovery) }),
+            )
+            .route("/jwks", get(|| async { Json(json!({ "keys": [] })) }))
+            .route("/token", post(token));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        issuer
+    }
+
+    async fn provider(authorized: &Authorized) -> OidcProvider {
+        let issuer = mock_provider(authorized.clone()).await;
+        OidcProvider::discover(OidcConfig {
+            issuer,
+            client_id: CLIENT_ID.to_string(),
+            client_secret: CLIENT_SECRET.to_string(),
+            redirect_url: "http://localhost:8000/api/oidc/callback".to_string(),
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Logs in at the mock provider, returns the state of the login and the value of its cookie
+    fn authorize(provider: &OidcProvider, authorized: &Authorized, code: &str) -> (String, String) {
+        let (url, login) = provider.authorize_url(now());
+        let query: HashMap<_, _> = url.query_pairs().into_owned().collect();
+        assert_eq!(query["client_id"], CLIENT_ID);
+        assert!(query.contains_key("code_challenge"));
+        authorized
+            .lock()
+            .unwrap()
+            .insert(code.to_string(), query["nonce"].clone());
+        (query["state"].clone(), login.encode())
+    }
+
+    #[tokio::test]
+    async fn test_login_at_provider() {
+        let authorized = Authorized::default();
+        let provider = provider(&authorized).await;
+        let (state, cookie) = authorize(&provider, &authorized, "code");
+        let login = PendingLogin::decode(&cookie);
+        let identity = provider
+            .identify("code", &state, login, now())
+            .await
+            .unwrap();
+        assert_eq!(identity.username, "bob");
+        assert_eq!(
+            identity.external_id,
+            format!("{}#248289761001", provider.issuer)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejected_logins() {
+        let authorized = Authorized::default();
+        let provider = provider(&authorized).await;
+        let (state, cookie) = authorize(&provider, &authorized, "code");
+        let login = || PendingLogin::decode(&cookie);
+        assert!(provider
+            .identify("code", "forged", login(), now())
+            .await
+            .is_err());
+        assert!(provider
+            .identify("code", &state, None, now())
+            .await
+            .is_err());
+        assert!(provider
+            .identify("code", &state, login(), now() + PENDING_TTL)
+            .await
+            .is_err());
+
+        // a login started in another browser is refused
+        let (other, _) = authorize(&provider, &authorized, "other");
+        assert!(provider
+            .identify("other", &other, login(), now())
+            .await
+            .is_err());
+        assert!(PendingLogin::decode("state.verifier.nonce").is_none());
+
+        // an ID token issued for a different login does not match its nonce
+        let (state, cookie) = authorize(&provider, &authorized, "code");
+        authorized
+            .lock()
+            .unwrap()
+            .insert("code".to_string(), "another nonce".to_string());
+        let error = provider
+            .identify("code", &state, PendingLogin::decode(&cookie), now())
+            .await
+            .unwrap_err();
+        let body: Value = serde_json::to_value(&error).unwrap();
+        assert_eq!(body["error"], "ProviderError");
+    }
+}
@@ -0,0 +1,207 @@
+//! Training sessions that issue exercises and record the answers given to them
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+};
+use puimuri_trainer::answer::ParsedAnswer;
+use puimuri_trainer::difficulty::Difficulty;
+use puimuri_trainer::equations::EquationExercise;
+use puimuri_trainer::progress::Progress;
+use puimuri_trainer::scheduler::LeitnerBox;
+use puimuri_trainer::session::{SessionStats, TrainingSession, SESSION_TTL};
+use puimuri_trainer::spec::ExerciseSpec;
+use puimuri_trainer::storage::Storage;
+use puimuri_trainer::TrainerError;
+use serde::Serialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::store::{Record, Store};
+use super::{ApiError, Json, Path, Query};
+use crate::{AnswerRequest, AnswerResponse, AppState};
+
+/// Seconds since the Unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// How many sessions are kept in memory
+pub const MAX_SESSIONS: usize = 10_000;
+
+/// Sessions kept in memory, shared by all request handlers
+///
+/// With a database every change is written through to it and sessions not in memory, e.g. after a restart, are loaded from it.
+/// Without one the least recently used session is forgotten when there are MAX_SESSIONS of them.
+pub type SessionStore = Store<TrainingSession>;
+
+impl Record for TrainingSession {
+    const NAME: &'static str = "sessions";
+    const TTL: Option<u64> = Some(SESSION_TTL);
+    const DISPOSABLE: bool = true;
+
+    fn unknown(id: &str) -> TrainerError {
+        TrainerError::UnknownSession(id.to_string())
+    }
+
+    fn is_expired(&self, now: u64) -> bool {
+        TrainingSession::is_expired(self, now)
+    }
+
+    fn save(&self, storage: &Storage) -> eyre::Result<()> {
+        storage.save_session(self)
+    }
+
+    fn load(storage: &Storage, id: &str) -> eyre::Result<Option<Self>> {
+        storage.load_session(id)
+    }
+}
+
+/// Progress of users kept in memory, shared by all request handlers
+///
+/// Every user has progress, a user who has none yet starts fresh. Without a database the least recently used progress is
+/// forgotten when there are MAX_SESSIONS of them.
+pub type ProgressStore = Store<Progress>;
+
+impl Record for Progress {
+    const NAME: &'static str = "progress";
+    const DISPOSABLE: bool = true;
+
+    fn unknown(id: &str) -> TrainerError {
+        TrainerError::UnknownUser(id.to_string())
+    }
+
+    fn fresh(id: &str, now: u64) -> Option<Self> {
+        Some(Progress::new(id, now))
+    }
+
+    fn save(&self, storage: &Storage) -> eyre::Result<()> {
+        storage.save_progress(self)
+    }
+
+    fn load(storage: &Storage, id: &str) -> eyre::Result<Option<Self>> {
+        storage.load_progress(id)
+    }
+}
+
+/// Identifier, current statistics, spaced repetition boxes and adaptive difficulty level of a session
+#[derive(Serialize)]
+pub struct SessionResponse {
+    id: String,
+    spec: ExerciseSpec,
+    stats: SessionStats,
+    boxes: Vec<LeitnerBox>,
+    difficulty: Difficulty,
+}
+
+impl From<&TrainingSession> for SessionResponse {
+    fn from(session: &TrainingSession) -> Self {
+        SessionResponse {
+            id: session.id.clone(),
+            spec: session.spec.clone(),
+            stats: session.stats(),
+            boxes: session.scheduler.boxes().to_vec(),
+            difficulty: session.adaptive.level,
+        }
+    }
+}
+
+/// Graded answer together with the statistics of the session after it
+#[derive(Serialize)]
+pub struct SessionAnswerResponse {
+    #[serde(flatten)]
+    response: AnswerResponse,
+    stats: SessionStats,
+}
+
+/// Starts a session, owned by the logged in user if there is one, the query parameters are the same as for a single exercise
+pub async fn create(
+    State(state): State<AppState>,
+    Query(spec): Query<ExerciseSpec>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<SessionResponse>), ApiError> {
+    // fail now rather than on the first exercise if the settings can not work
+    spec.builder()?;
+    let mut session = TrainingSession::new(spec, now());
+    // the progress of a logged in user is kept with their account
+    if let Some(user) = state.user(&headers) {
+        let progress = state
+            .progress
+            .update(&user.id, |progress| Ok(progress.clone()))
+            .await?;
+        session.resume(&progress);
+    }
+    let response = SessionResponse::from(&session);
+    state.sessions.insert(session.id.clone(), session).await?;
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// Returns the statistics of the session
+pub async fn show(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<(StatusCode, Json<SessionResponse>), ApiError> {
+    let response = state
+        .sessions
+        .update(&id, |session| Ok(SessionResponse::from(&*session)))
+        .await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Issues the next exercise of the session
+pub async fn exercise(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<EquationExercise>), ApiError> {
+    let mut exercise = state
+        .sessions
+        .update(&id, |session| Ok(session.next_exercise(now())?))
+        .await?;
+    state.hand_out(&mut exercise, &headers, Some(&id)).await?;
+    Ok((StatusCode::OK, Json(exercise)))
+}
+
+/// Grades and records the answer to an exercise issued in the session and in the progress of its user
+pub async fn answer(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<AnswerRequest>,
+) -> Result<(StatusCode, Json<SessionAnswerResponse>), ApiError> {
+    let answer = ParsedAnswer::parse(&request.answer)?;
+    let response = state.grade(&request.exercise, &answer, Some(&id))?;
+    let (exercise, grade, score) = (
+        request.exercise.clone(),
+        response.grade.clone(),
+        response.score.clone(),
+    );
+    let (stats, user_id, level) = state
+        .sessions
+        .update(&id, move |session| {
+            session.record(&exercise, &grade, &score, now())?;
+            let level = session.spec.difficulty.unwrap_or_default();
+            Ok((session.stats(), session.user_id.clone(), level))
+        })
+        .await?;
+    if let Some(user_id) = user_id {
+        let (exercise, correct) = (request.exercise.clone(), response.grade.is_correct());
+        state
+            .progress
+            .update(&user_id, move |progress| {
+                progress.record(&exercise, correct, level, now());
+                Ok(())
+            })
+            .await?;
+    }
+    state
+        .record(&request.exercise, Some(&id), &request.answer, &response)
+        .await?;
+    let status = if response.grade.is_correct() {
+        StatusCode::OK
+    } else {
+        StatusCode::PRECONDITION_FAILED
+    };
+    Ok((status, Json(SessionAnswerResponse { response, stats })))
+}
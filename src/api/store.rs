@@ -0,0 +1,304 @@
+//! Records kept in memory and written through to the database, shared by all request handlers
+
+use axum::http::StatusCode;
+use puimuri_trainer::storage::Storage;
+use puimuri_trainer::TrainerError;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::session::now;
+use super::{blocking, ApiError};
+
+/// Value kept in a store and saved to the database after every change
+pub trait Record: Sized + Send + 'static {
+    /// Name of the records in messages, e.g. "sessions"
+    const NAME: &'static str;
+
+    /// Seconds a record may go unused before it is dropped from memory, None to keep records until the store is full
+    const TTL: Option<u64> = None;
+
+    /// Whether a full store without a database may forget records instead of refusing new ones
+    const DISPOSABLE: bool = false;
+
+    /// Error for a record that does not exist
+    fn unknown(id: &str) -> TrainerError;
+
+    /// Record to start from when none exists, None if records must be inserted before they are used
+    fn fresh(_id: &str, _now: u64) -> Option<Self> {
+        None
+    }
+
+    /// Whether the record has expired and is treated as if it did not exist
+    fn is_expired(&self, _now: u64) -> bool {
+        false
+    }
+
+    /// Inserts or updates the record in the database
+    fn save(&self, storage: &Storage) -> eyre::Result<()>;
+
+    /// Record from the database, None if it does not exist
+    fn load(storage: &Storage, id: &str) -> eyre::Result<Option<Self>>;
+}
+
+/// Record in memory together with when it was last used
+struct Entry<T> {
+    record: Arc<tokio::sync::Mutex<T>>,
+    used_at: u64,
+    /// Number of the last use among all uses of the store, orders uses within the same second
+    use_count: u64,
+}
+
+impl<T> Entry<T> {
+    fn touch(&mut self, now: u64, uses: &AtomicU64) {
+        self.used_at = now;
+        self.use_count = uses.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Records by their identifiers, each behind a lock of its own
+///
+/// The map is only locked to find a record, so a slow change or database write holds up requests to that record alone.
+/// At most `capacity` records are kept in memory. With a database the least recently used ones make room, as they can be
+/// loaded again.
+pub struct Store<T> {
+    entries: Arc<Mutex<HashMap<String, Entry<T>>>>,
+    storage: Option<Arc<Storage>>,
+    capacity: usize,
+    uses: Arc<AtomicU64>,
+}
+
+impl<T> Clone for Store<T> {
+    fn clone(&self) -> Self {
+        Store {
+            entries: self.entries.clone(),
+            storage: self.storage.clone(),
+            capacity: self.capacity,
+            uses: self.uses.clone(),
+        }
+    }
+}
+
+impl<T: Record> Store<T> {
+    /// Creates an empty store, optionally backed by a database
+    pub fn new(storage: Option<Arc<Storage>>, capacity: usize) -> Store<T> {
+        Store {
+            entries: Arc::default(),
+            storage,
+            capacity,
+            uses: Arc::default(),
+        }
+    }
+
+    /// Adds a new record, saving it first so that records in memory are always in the database as well
+    pub async fn insert(&self, id: String, record: T) -> Result<(), ApiError> {
+        let record = match self.storage.clone() {
+            Some(storage) => {
+                blocking(move || {
+                    record.save(&storage)?;
+                    Ok(record)
+                })
+                .await?
+            }
+            None => record,
+        };
+        self.admit(id, record)?;
+        Ok(())
+    }
+
+    /// Runs the function on the record and saves it, failing if the record does not exist or has expired
+    ///
+    /// Both run on a blocking thread, as generating exercises and writing to the database can take a while.
+    pub async fn update<R: Send + 'static>(
+        &self,
+        id: &str,
+        f: impl FnOnce(&mut T) -> Result<R, ApiError> + Send + 'static,
+    ) -> Result<R, ApiError> {
+        let mut record = self.get(id).await?.lock_owned().await;
+        if record.is_expired(now()) {
+            self.entries.lock().unwrap().remove(id);
+            return Err(T::unknown(id).into());
+        }
+        let storage = self.storage.clone();
+        blocking(move || {
+            let result = f(&mut record)?;
+            if let Some(storage) = storage {
+                record.save(&storage)?;
+            }
+            Ok(result)
+        })
+        .await
+    }
+
+    /// Record from memory, else from the database or else a fresh one
+    async fn get(&self, id: &str) -> Result<Arc<tokio::sync::Mutex<T>>, ApiError> {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(id) {
+            entry.touch(now(), &self.uses);
+            return Ok(entry.record.clone());
+        }
+        let loaded = match self.storage.clone() {
+            Some(storage) => {
+                let key = id.to_string();
+                blocking(move || Ok(T::load(&storage, &key)?)).await?
+            }
+            None => None,
+        };
+        match loaded
+            .filter(|record| !record.is_expired(now()))
+            .or_else(|| T::fresh(id, now()))
+        {
+            Some(record) => self.admit(id.to_string(), record),
+            None => Err(T::unknown(id).into()),
+        }
+    }
+
+    /// Keeps the record in memory, another request may have loaded it meanwhile and then both use the first one
+    fn admit(&self, id: String, record: T) -> Result<Arc<tokio::sync::Mutex<T>>, ApiError> {
+        let now = now();
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.get_mut(&id) {
+            entry.touch(now, &self.uses);
+            return Ok(entry.record.clone());
+        }
+        self.make_room(&mut entries, now)?;
+        let record = Arc::new(tokio::sync::Mutex::new(record));
+        entries.insert(
+            id,
+            Entry {
+                record: record.clone(),
+                used_at: now,
+                use_count: self.uses.fetch_add(1, Ordering::Relaxed),
+            },
+        );
+        Ok(record)
+    }
+
+    /// Drops expired records and then the least recently used one if the store is still full
+    fn make_room(&self, entries: &mut HashMap<String, Entry<T>>, now: u64) -> Result<(), ApiError> {
+        if entries.len() < self.capacity {
+            return Ok(());
+        }
+        if let Some(ttl) = T::TTL {
+            entries.retain(|_, entry| now.saturating_sub(entry.used_at) <= ttl);
+        }
+        if entries.len() < self.capacity {
+            return Ok(());
+        }
+        if self.storage.is_none() && !T::DISPOSABLE {
+            return Err(ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "StoreFull",
+                format!("Too many {} are kept in memory, try again later", T::NAME),
+            ));
+        }
+        let oldest = entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.use_count)
+            .map(|(id, _)| id.clone());
+        if let Some(oldest) = oldest {
+            entries.remove(&oldest);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use puimuri_trainer::accounts::{Role, User};
+    use puimuri_trainer::progress::Progress;
+    use puimuri_trainer::session::TrainingSession;
+    use puimuri_trainer::spec::ExerciseSpec;
+
+    /// Record that can not be loaded again once forgotten
+    struct Note(String);
+
+    impl Record for Note {
+        const NAME: &'static str = "notes";
+
+        fn unknown(id: &str) -> TrainerError {
+            TrainerError::UnknownExam(id.to_string())
+        }
+
+        fn save(&self, _storage: &Storage) -> eyre::Result<()> {
+            Ok(())
+        }
+
+        fn load(_storage: &Storage, _id: &str) -> eyre::Result<Option<Self>> {
+            Ok(None)
+        }
+    }
+
+    fn kind(error: ApiError) -> String {
+        serde_json::to_value(error).unwrap()["error"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_full_store() {
+        let notes = Store::new(None, 2);
+        for id in ["a", "b"] {
+            notes
+                .insert(id.to_string(), Note(id.to_string()))
+                .await
+                .unwrap();
+        }
+        let error = notes.insert("c".to_string(), Note("c".to_string())).await;
+        assert_eq!(kind(error.unwrap_err()), "StoreFull");
+        let note = notes.update("a", |note| Ok(note.0.clone())).await;
+        assert_eq!(note.unwrap(), "a");
+
+        // sessions are forgotten instead, least recently used first
+        let sessions = Store::new(None, 2);
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            let session = TrainingSession::new(ExerciseSpec::default(), now());
+            ids.push(session.id.clone());
+            sessions.insert(session.id.clone(), session).await.unwrap();
+        }
+        let error = sessions.update(&ids[0], |_| Ok(())).await;
+        assert_eq!(kind(error.unwrap_err()), "UnknownSession");
+        assert!(sessions.update(&ids[2], |_| Ok(())).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_fresh_records() {
+        let storage = Arc::new(Storage::open_in_memory().unwrap());
+        storage
+            .save_user(&User {
+                id: "ada".to_string(),
+                username: "Ada".to_string(),
+                role: Role::Student,
+                password_hash: String::new(),
+                external_id: None,
+                created_at: 0,
+            })
+            .unwrap();
+        // users without progress start fresh
+        let progress: Store<Progress> = Store::new(Some(storage.clone()), 10);
+        let user_id = progress
+            .update("ada", |progress| Ok(progress.user_id.clone()))
+            .await
+            .unwrap();
+        assert_eq!(user_id, "ada");
+        assert!(storage.load_progress("ada").unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_expired_sessions_are_unknown() {
+        let storage = Arc::new(Storage::open_in_memory().unwrap());
+        let sessions = Store::new(Some(storage.clone()), 10);
+        let session = TrainingSession::new(ExerciseSpec::default(), 0);
+        let id = session.id.clone();
+        sessions.insert(id.clone(), session.clone()).await.unwrap();
+        let error = sessions.update(&id, |_| Ok(())).await;
+        assert_eq!(kind(error.unwrap_err()), "UnknownSession");
+
+        // nor are they loaded again from the database
+        storage.save_session(&session).unwrap();
+        let error = sessions.update(&id, |_| Ok(())).await;
+        assert_eq!(kind(error.unwrap_err()), "UnknownSession");
+    }
+}
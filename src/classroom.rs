@@ -0,0 +1,424 @@
+#![warn(missing_docs)]
+
+//! PUImURI trainer for Ohms law, power equation and their combinations
+//!
+//! Classes that students join with a code, with assignments generated from exercise settings and the results of every student
+
+use eyre::{Context, Result};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::equations::EquationExercise;
+use crate::exam::{grade_answers, total_score, ExamAnswer, Submission};
+use crate::grading::GradingPolicy;
+use crate::scoring::ScoringModel;
+use crate::spec::ExerciseSetSpec;
+use crate::TrainerError;
+
+/// Characters of join codes, without ones that are easily mixed up like 0 and O
+const JOIN_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// Length of join codes
+const JOIN_CODE_LENGTH: usize = 6;
+
+/// How many students can join one class
+pub const MAX_STUDENTS: usize = 500;
+
+/// Random code students join a class with, e.g. "K7QX2M"
+pub fn join_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..JOIN_CODE_LENGTH)
+        .map(|_| *JOIN_CODE_ALPHABET.choose(&mut rng).unwrap() as char)
+        .collect()
+}
+
+/// Student enrolled in a class
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Student {
+    /// Name of the student
+    pub name: String,
+    /// When the student joined, in seconds since the Unix epoch
+    pub joined_at: u64,
+}
+
+/// Settings of an assignment as given by the teacher
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct AssignmentSpec {
+    /// Title shown to the students
+    pub title: String,
+    /// Exercises of the assignment
+    #[serde(flatten)]
+    pub set: ExerciseSetSpec,
+    /// When the assignment is due, in seconds since the Unix epoch
+    pub due_at: u64,
+}
+
+/// Latest answers of a student to an assignment
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct AssignmentSubmission {
+    /// Graded answers
+    #[serde(flatten)]
+    pub submission: Submission,
+    /// Whether the answers were submitted after the due date
+    pub late: bool,
+}
+
+/// Set of exercises every student of the class answers
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Assignment {
+    /// Random identifier of the assignment
+    pub id: String,
+    /// Settings of the assignment
+    pub spec: AssignmentSpec,
+    /// When the assignment was created, in seconds since the Unix epoch
+    pub created_at: u64,
+    /// Exercises of the assignment, the same for every student
+    pub exercises: Vec<EquationExercise>,
+    /// Latest submission of every student that has answered
+    #[serde(default)]
+    pub submissions: Vec<AssignmentSubmission>,
+}
+
+impl Assignment {
+    /// Generates the exercises of the assignment, which can take a while with strict ranges
+    pub fn new(mut spec: AssignmentSpec, now: u64) -> Result<Assignment> {
+        // a fixed seed lets the assignment be generated again from its settings
+        spec.set.exercise.seed = Some(
+            spec.set
+                .exercise
+                .seed
+                .unwrap_or_else(|| rand::thread_rng().gen()),
+        );
+        let exercises = spec
+            .set
+            .build()
+            .with_context(|| format!("Unable to generate assignment \"{}\"", spec.title))?;
+        Ok(Assignment {
+            id: format!("{:016x}", rand::thread_rng().gen::<u64>()),
+            spec,
+            created_at: now,
+            exercises,
+            submissions: Vec::new(),
+        })
+    }
+
+    /// Latest submission of the student
+    pub fn submission(&self, student: &str) -> Option<&AssignmentSubmission> {
+        self.submissions
+            .iter()
+            .find(|submission| submission.submission.student == student)
+    }
+}
+
+/// Results of one student in every assignment of the class
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct StudentResults {
+    /// Name of the student
+    pub student: String,
+    /// Results in the order of the assignments, None for assignments not submitted
+    pub assignments: Vec<Option<AssignmentResult>>,
+    /// Sum of the scores of every assignment
+    pub score: f64,
+}
+
+/// Summary of the submission of one student to one assignment
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct AssignmentResult {
+    /// Identifier of the assignment
+    pub assignment_id: String,
+    /// Exercises answered correctly
+    pub correct: usize,
+    /// Sum of the partial credit of every exercise
+    pub score: f64,
+    /// When the answers were submitted, in seconds since the Unix epoch
+    pub submitted_at: u64,
+    /// Whether the answers were submitted after the due date
+    pub late: bool,
+}
+
+/// Class of a teacher
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Classroom {
+    /// Random identifier of the class
+    pub id: String,
+    /// Identifier of the teacher who created the class, None if created with the teacher key
+    #[serde(default)]
+    pub owner_id: Option<String>,
+    /// Name of the class, e.g. "Electricians 2nd year"
+    pub name: String,
+    /// Code students join the class with
+    pub join_code: String,
+    /// When the class was created, in seconds since the Unix epoch
+    pub created_at: u64,
+    /// Enrolled students in the order they joined
+    #[serde(default)]
+    pub students: Vec<Student>,
+    /// Assignments in the order they were created
+    #[serde(default)]
+    pub assignments: Vec<Assignment>,
+}
+
+impl Classroom {
+    /// Creates an empty class with a fresh join code
+    pub fn new(name: &str, now: u64) -> Classroom {
+        Classroom {
+            id: format!("{:032x}", rand::thread_rng().gen::<u128>()),
+            owner_id: None,
+            name: name.to_string(),
+            join_code: join_code(),
+            created_at: now,
+            students: Vec::new(),
+            assignments: Vec::new(),
+        }
+    }
+
+    /// Enrols the student, joining again changes nothing
+    pub fn join(&mut self, student: &str, now: u64) -> Result<&Student, TrainerError> {
+        let position = match self.student_position(student) {
+            Some(position) => position,
+            None if self.students.len() >= MAX_STUDENTS => {
+                return Err(TrainerError::ClassFull(self.id.clone()));
+            }
+            None => {
+                self.students.push(Student {
+                    name: student.to_string(),
+                    joined_at: now,
+                });
+                self.students.len() - 1
+            }
+        };
+        Ok(&self.students[position])
+    }
+
+    fn student_position(&self, student: &str) -> Option<usize> {
+        self.students
+            .iter()
+            .position(|enrolled| enrolled.name == student)
+    }
+
+    /// Fails unless the student has joined the class
+    pub fn require_student(&self, student: &str) -> Result<(), TrainerError> {
+        match self.student_position(student) {
+            Some(_) => Ok(()),
+            None => Err(TrainerError::NotEnrolled(student.to_string())),
+        }
+    }
+
+    /// Generates the exercises of a new assignment
+    pub fn assign(&mut self, spec: AssignmentSpec, now: u64) -> Result<&Assignment> {
+        let assignment = Assignment::new(spec, now)?;
+        Ok(self.add_assignment(assignment))
+    }
+
+    /// Adds an assignment generated beforehand
+    pub fn add_assignment(&mut self, assignment: Assignment) -> &Assignment {
+        self.assignments.push(assignment);
+        self.assignments.last().unwrap()
+    }
+
+    /// Assignment of the class
+    pub fn assignment(&self, id: &str) -> Result<&Assignment, TrainerError> {
+        self.assignments
+            .iter()
+            .find(|assignment| assignment.id == id)
+            .ok_or_else(|| TrainerError::UnknownAssignment(id.to_string()))
+    }
+
+    /// Grades the answers of an enrolled student, replacing their earlier submission unless that was on time and this
+    /// one is late
+    pub fn submit(
+        &mut self,
+        assignment_id: &str,
+        student: &str,
+        answers: &[ExamAnswer],
+        policy: GradingPolicy,
+        scoring: &ScoringModel,
+        now: u64,
+    ) -> Result<&AssignmentSubmission> {
+        self.require_student(student)?;
+        let position = self
+            .assignments
+            .iter()
+            .position(|assignment| assignment.id == assignment_id)
+            .ok_or_else(|| TrainerError::UnknownAssignment(assignment_id.to_string()))?;
+        let assignment = &mut self.assignments[position];
+        let late = now > assignment.spec.due_at;
+        // teachers keep the answers given on time
+        if late
+            && assignment
+                .submission(student)
+                .is_some_and(|earlier| !earlier.late)
+        {
+            return Err(TrainerError::SubmittedOnTime(assignment_id.to_string()).into());
+        }
+
+        let results = grade_answers(&assignment.exercises, answers, policy, scoring)?;
+        let submission = AssignmentSubmission {
+            submission: Submission {
+                student: student.to_string(),
+                submitted_at: now,
+                score: total_score(&results),
+                results,
+            },
+            late,
+        };
+        assignment
+            .submissions
+            .retain(|earlier| earlier.submission.student != student);
+        assignment.submissions.push(submission);
+        Ok(assignment.submissions.last().unwrap())
+    }
+
+    /// Results of every student in every assignment
+    pub fn results(&self) -> Vec<StudentResults> {
+        self.students
+            .iter()
+            .map(|student| {
+                let assignments: Vec<_> = self
+                    .assignments
+                    .iter()
+                    .map(|assignment| {
+                        assignment
+                            .submission(&student.name)
+                            .map(|submission| AssignmentResult {
+                                assignment_id: assignment.id.clone(),
+                                correct: submission
+                                    .submission
+                                    .results
+                                    .iter()
+                                    .filter(|result| result.correct)
+                                    .count(),
+                                score: submission.submission.score,
+                                submitted_at: submission.submission.submitted_at,
+                                late: submission.late,
+                            })
+                    })
+                    .collect();
+                StudentResults {
+                    student: student.name.clone(),
+                    score: assignments
+                        .iter()
+                        .flatten()
+                        .fold(0.0, |score, result| score + result.score),
+                    assignments,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assignment_spec(count: usize, due_at: u64) -> AssignmentSpec {
+        AssignmentSpec {
+            title: "Ohms law".to_string(),
+            set: ExerciseSetSpec {
+                count,
+                ..ExerciseSetSpec::default()
+            },
+            due_at,
+        }
+    }
+
+    fn answers(assignment: &Assignment, right: usize) -> Vec<ExamAnswer> {
+        assignment.exercises[..right]
+            .iter()
+            .map(|exercise| ExamAnswer {
+                exercise_id: exercise.id.clone(),
+                answer: exercise.solve().unwrap().answer.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_join_codes() {
+        let code = join_code();
+        assert_eq!(code.len(), JOIN_CODE_LENGTH);
+        assert!(code.bytes().all(|byte| JOIN_CODE_ALPHABET.contains(&byte)));
+        assert_ne!(
+            Classroom::new("a", 0).join_code,
+            Classroom::new("a", 0).join_code
+        );
+    }
+
+    #[test]
+    fn test_enrolment() {
+        let mut class = Classroom::new("Electricians", 0);
+        assert!(class.require_student("ada").is_err());
+        assert_eq!(class.join("ada", 10).unwrap().joined_at, 10);
+        assert_eq!(class.join("ada", 20).unwrap().joined_at, 10);
+        assert_eq!(class.students.len(), 1);
+        assert!(class.require_student("ada").is_ok());
+
+        for student in 1..MAX_STUDENTS {
+            class.join(&student.to_string(), 30).unwrap();
+        }
+        assert!(matches!(
+            class.join("bob", 40),
+            Err(TrainerError::ClassFull(_))
+        ));
+        // enrolled students can still join again
+        assert!(class.join("ada", 40).is_ok());
+    }
+
+    #[test]
+    fn test_assignments_and_results() {
+        let mut class = Classroom::new("Electricians", 0);
+        class.join("ada", 0).unwrap();
+        class.join("bob", 0).unwrap();
+        let first = class.assign(assignment_spec(4, 100), 0).unwrap().clone();
+        let second = class.assign(assignment_spec(2, 200), 0).unwrap().clone();
+        assert_eq!(first.exercises.len(), 4);
+        assert!(class.assignment(&second.id).is_ok());
+        assert!(class.assignment("nothing").is_err());
+
+        let policy = GradingPolicy::default();
+        let scoring = ScoringModel::default();
+        class
+            .submit(&first.id, "ada", &answers(&first, 1), policy, &scoring, 50)
+            .unwrap();
+        // resubmitting replaces the earlier answers until the due date
+        let submission = class
+            .submit(&first.id, "ada", &answers(&first, 3), policy, &scoring, 60)
+            .unwrap();
+        assert!(!submission.late);
+        assert_eq!(class.assignments[0].submissions.len(), 1);
+        let error = class
+            .submit(&first.id, "ada", &answers(&first, 4), policy, &scoring, 150)
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(TrainerError::SubmittedOnTime(_))
+        ));
+        // students who missed the due date still can answer, marked late
+        let submission = class
+            .submit(&first.id, "bob", &answers(&first, 2), policy, &scoring, 150)
+            .unwrap();
+        assert!(submission.late);
+        class
+            .submit(
+                &second.id,
+                "ada",
+                &answers(&second, 2),
+                policy,
+                &scoring,
+                150,
+            )
+            .unwrap();
+        assert!(class
+            .submit(&first.id, "cy", &answers(&first, 1), policy, &scoring, 50)
+            .is_err());
+
+        let results = class.results();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].student, "ada");
+        assert_eq!(results[0].score, 5.0);
+        assert_eq!(results[0].assignments[0].as_ref().unwrap().correct, 3);
+        assert!(!results[0].assignments[1].as_ref().unwrap().late);
+        assert!(results[1].assignments[0].as_ref().unwrap().late);
+        assert_eq!(results[1].assignments[1], None);
+    }
+}
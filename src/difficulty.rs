@@ -0,0 +1,191 @@
+#![warn(missing_docs)]
+
+//! PUImURI trainer for Ohms law, power equation and their combinations
+//!
+//! Difficulty levels from 1 to 10 that drive the exercise type mix, value ranges, decimals and prefixes
+
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::equations::{EquationExerciseType, EquationVariable};
+use crate::TrainerError;
+
+/// Difficulty level of generated exercises, 1 is the easiest and 10 the hardest
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(try_from = "u8", into = "u8")]
+pub struct Difficulty(u8);
+
+impl Difficulty {
+    /// Easiest level
+    pub const MIN: Difficulty = Difficulty(1);
+    /// Hardest level
+    pub const MAX: Difficulty = Difficulty(10);
+
+    /// Creates a difficulty level, level must be between 1 and 10
+    pub fn new(level: u8) -> Result<Difficulty, TrainerError> {
+        if (Self::MIN.0..=Self::MAX.0).contains(&level) {
+            Ok(Difficulty(level))
+        } else {
+            Err(TrainerError::InvalidDifficulty(level.to_string()))
+        }
+    }
+
+    /// Numeric level between 1 and 10
+    pub fn level(&self) -> u8 {
+        self.0
+    }
+
+    /// Next level up, the hardest level stays as is
+    pub fn harder(&self) -> Difficulty {
+        Difficulty((self.0 + 1).min(Self::MAX.0))
+    }
+
+    /// Next level down, the easiest level stays as is
+    pub fn easier(&self) -> Difficulty {
+        Difficulty((self.0 - 1).max(Self::MIN.0))
+    }
+
+    /// Exercise types mixed on this level, combined exercises need more steps so they come last
+    pub fn exercise_types(&self) -> &'static [EquationExerciseType] {
+        match self.0 {
+            1..=2 => &[EquationExerciseType::OhmsLaw],
+            3..=4 => &[EquationExerciseType::OhmsLaw, EquationExerciseType::Power],
+            _ => &[
+                EquationExerciseType::OhmsLaw,
+                EquationExerciseType::Power,
+                EquationExerciseType::Combined,
+            ],
+        }
+    }
+
+    /// Range values of the variable are drawn from on this level
+    pub fn range(&self, variable: EquationVariable) -> (f64, f64) {
+        let ranges = match self.0 {
+            1..=3 => [(1.0, 24.0), (1.0, 5.0), (1.0, 20.0), (1.0, 100.0)],
+            4..=7 => [(1.0, 240.0), (0.1, 10.0), (1.0, 1000.0), (1.0, 2400.0)],
+            _ => [
+                (1.0, 1000.0),
+                (0.001, 10.0),
+                (1.0, 1_000_000.0),
+                (0.01, 2400.0),
+            ],
+        };
+        // indexed in the declaration order of EquationVariable: voltage, current, resistance, power
+        ranges[variable as usize]
+    }
+
+    /// Whether values are presented with SI prefixes, which adds conversion steps
+    pub fn use_prefixes(&self) -> bool {
+        self.0 >= 6
+    }
+
+    /// Decimals of the given values
+    pub fn given_decimals(&self) -> u32 {
+        match self.0 {
+            1..=5 => 0,
+            _ => 1,
+        }
+    }
+
+    /// Maximum decimals of the answer, None means the answer is whatever the given values produce
+    pub fn answer_decimals(&self) -> Option<u32> {
+        match self.0 {
+            1..=3 => Some(0),
+            4..=6 => Some(1),
+            7..=8 => Some(2),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Difficulty(5)
+    }
+}
+
+impl TryFrom<u8> for Difficulty {
+    type Error = TrainerError;
+
+    fn try_from(level: u8) -> Result<Self, Self::Error> {
+        Difficulty::new(level)
+    }
+}
+
+impl From<Difficulty> for u8 {
+    fn from(difficulty: Difficulty) -> Self {
+        difficulty.0
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = TrainerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim()
+            .parse::<u8>()
+            .map_err(|_| TrainerError::InvalidDifficulty(s.to_string()))
+            .and_then(Difficulty::new)
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equations::EquationExerciseBuilder;
+
+    #[test]
+    fn test_difficulty_levels() {
+        assert!(Difficulty::new(0).is_err());
+        assert!(Difficulty::new(11).is_err());
+        assert_eq!(Difficulty::new(7).unwrap().level(), 7);
+        assert_eq!(
+            "3".parse::<Difficulty>().unwrap(),
+            Difficulty::new(3).unwrap()
+        );
+        assert!("hard".parse::<Difficulty>().is_err());
+        assert!(Difficulty::MIN < Difficulty::MAX);
+        assert_eq!(Difficulty::MIN.harder().level(), 2);
+        assert_eq!(Difficulty::MIN.easier(), Difficulty::MIN);
+        assert_eq!(Difficulty::MAX.harder(), Difficulty::MAX);
+    }
+
+    #[test]
+    fn test_build_every_difficulty() {
+        for level in Difficulty::MIN.level()..=Difficulty::MAX.level() {
+            let difficulty = Difficulty::new(level).unwrap();
+            for _ in 0..30 {
+                let exercise = EquationExerciseBuilder::new()
+                    .set_difficulty(difficulty)
+                    .build_with_random_exercisetype()
+                    .unwrap();
+                assert!(difficulty
+                    .exercise_types()
+                    .contains(&exercise.exercise_type));
+                assert_eq!(!exercise.prefixes.is_empty(), difficulty.use_prefixes());
+                exercise.solve().unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_beginner_exercises() {
+        for _ in 0..30 {
+            let exercise = EquationExerciseBuilder::new()
+                .set_difficulty(Difficulty::MIN)
+                .build_with_random_exercisetype()
+                .unwrap();
+            assert_eq!(exercise.exercise_type, EquationExerciseType::OhmsLaw);
+            let answer = exercise.solve().unwrap().answer;
+            assert_eq!(answer, answer.round(), "{:?}", exercise);
+        }
+    }
+}
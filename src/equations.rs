@@ -5,9 +5,14 @@
 //! This library provides a trainer implementation that creates and solves excercesis
 
 use eyre::{Context, Result};
-use rand::{rngs::ThreadRng, seq::SliceRandom, Rng};
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
+use crate::difficulty::Difficulty;
+use crate::grading::GradingPolicy;
+use crate::units::{format_quantity, round_to, SiPrefix};
 use crate::TrainerError;
 
 /// What type of an excerise is in question?
@@ -22,6 +27,49 @@ pub enum EquationExerciseType {
     Combined,
 }
 
+impl EquationExerciseType {
+    /// Variables that can be asked in this type of an exercise
+    pub fn missing_variables(&self) -> &'static [EquationVariable] {
+        match self {
+            EquationExerciseType::OhmsLaw => &[
+                EquationVariable::Voltage,
+                EquationVariable::Current,
+                EquationVariable::Resistance,
+            ],
+            EquationExerciseType::Power => &[
+                EquationVariable::Power,
+                EquationVariable::Voltage,
+                EquationVariable::Current,
+            ],
+            EquationExerciseType::Combined => &[
+                EquationVariable::Power,
+                EquationVariable::Current,
+                EquationVariable::Resistance,
+            ],
+        }
+    }
+
+    /// Variables given to the user when the missing variable is asked, in the order they are presented
+    pub fn given_variables(
+        &self,
+        missing_variable: EquationVariable,
+    ) -> Option<[EquationVariable; 2]> {
+        use EquationVariable::*;
+        match (self, missing_variable) {
+            (EquationExerciseType::OhmsLaw, Voltage) => Some([Resistance, Current]),
+            (EquationExerciseType::OhmsLaw, Current) => Some([Voltage, Resistance]),
+            (EquationExerciseType::OhmsLaw, Resistance) => Some([Voltage, Current]),
+            (EquationExerciseType::Power, Power) => Some([Voltage, Current]),
+            (EquationExerciseType::Power, Voltage) => Some([Power, Current]),
+            (EquationExerciseType::Power, Current) => Some([Power, Voltage]),
+            (EquationExerciseType::Combined, Power) => Some([Voltage, Resistance]),
+            (EquationExerciseType::Combined, Current) => Some([Power, Resistance]),
+            (EquationExerciseType::Combined, Resistance) => Some([Power, Voltage]),
+            _ => None,
+        }
+    }
+}
+
 /// What type of variable are we handling?
 #[derive(PartialEq, Copy, Clone, Debug, Default, Serialize, Deserialize)]
 pub enum EquationVariable {
@@ -36,8 +84,30 @@ pub enum EquationVariable {
     Power,
 }
 
+impl EquationVariable {
+    /// Unit that the variable is measured in
+    pub fn unit(&self) -> EquationUnit {
+        match self {
+            EquationVariable::Voltage => EquationUnit::Volt,
+            EquationVariable::Current => EquationUnit::Ampere,
+            EquationVariable::Resistance => EquationUnit::Ohm,
+            EquationVariable::Power => EquationUnit::Watt,
+        }
+    }
+
+    /// Symbol of the variable as it is written in the equations
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            EquationVariable::Voltage => "U",
+            EquationVariable::Current => "I",
+            EquationVariable::Resistance => "R",
+            EquationVariable::Power => "P",
+        }
+    }
+}
+
 /// What type of an unit is the ExerciseSolution unit in
-#[derive(Debug, Clone, Serialize, PartialEq)]
+#[derive(Copy, Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum EquationUnit {
     /// Volts
     Volt,
@@ -49,27 +119,57 @@ pub enum EquationUnit {
     Watt,
 }
 
+impl EquationUnit {
+    /// Symbol of the unit as it is written after the value
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            EquationUnit::Volt => "V",
+            EquationUnit::Ampere => "A",
+            EquationUnit::Ohm => "Ω",
+            EquationUnit::Watt => "W",
+        }
+    }
+}
+
 /// Contains the solution and work needed to reach that answer for a spesific Exercise
 #[derive(Debug, Clone, Serialize, PartialEq)]
 pub struct EquationExerciseSolution {
     /// Shows the work needed to reach the answer
     pub steps: Vec<String>,
-    /// Contains the answer to the exercise
+    /// Contains the answer to the exercise in the base unit
     pub answer: f64,
     /// Unit type of the answer
     pub unit: EquationUnit,
+    /// SI prefix the answer is requested in
+    pub prefix: SiPrefix,
+}
+
+impl EquationExerciseSolution {
+    /// Returns the answer converted to the requested SI prefix
+    pub fn prefixed_answer(&self) -> f64 {
+        self.prefix.from_base(self.answer)
+    }
 }
 
 /// An Excersise that user must solve or which is to be explained to the user
 #[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
 pub struct EquationExercise {
+    /// Stable identifier derived from the content of the exercise, see fingerprint()
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    /// Signature issued by the server that proves the exercise has not been modified, see ExerciseSigner
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
     /// Type of the exercise
     pub exercise_type: EquationExerciseType,
     /// What variable is missing in the equation that user needs to solve
     pub missing_variable: EquationVariable,
-    /// What other variables in the equation are already known
+    /// What other variables in the equation are already known, always in base units
     pub given_variables: Vec<(EquationVariable, f64)>,
-    /// What is the correct answer for this exercise
+    /// SI prefixes the given variables are presented in and the missing variable is requested in, unlisted variables use the base unit
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prefixes: Vec<(EquationVariable, SiPrefix)>,
+    /// What is the correct answer for this exercise, only filled in by reveal() so that it is not sent to every client
     #[serde(skip_serializing_if = "Option::is_none", skip_deserializing)]
     pub correct_answer: Option<f64>,
 }
@@ -80,16 +180,69 @@ impl EquationExercise {
         EquationExerciseBuilder::new()
     }
 
-    /// Check the answer
-    pub fn check_answer(&self, user_answer: f64, precision: Option<f64>) -> Option<bool> {
-        if let Some(correct_answer) = self.correct_answer {
-            if let Some(precision) = precision {
-                return Some((user_answer - correct_answer).abs() < precision);
-            } else {
-                return Some((user_answer - correct_answer).abs() < 0.1);
-            }
+    /// Identifier of the problem, the same for equal exercises no matter how or when they were generated
+    pub fn fingerprint(&self) -> String {
+        hex::encode(&Sha256::digest(self.canonical().as_bytes())[..8])
+    }
+
+    /// Canonical text form of the content of the exercise that fingerprints and signatures are calculated from
+    pub(crate) fn canonical(&self) -> String {
+        let mut given_variables = self.given_variables.clone();
+        given_variables.sort_by_key(|(variable, _)| *variable as u8);
+        let mut canonical = format!("{:?}|{:?}", self.exercise_type, self.missing_variable);
+        for (variable, value) in given_variables {
+            canonical.push_str(&format!(
+                "|{:?}={:?}{}",
+                variable,
+                value,
+                self.prefix_for(variable).symbol()
+            ));
         }
-        None
+        canonical.push_str(&format!(
+            "|{}",
+            self.prefix_for(self.missing_variable).symbol()
+        ));
+        canonical
+    }
+
+    /// Returns the SI prefix that the variable is presented or requested in
+    pub fn prefix_for(&self, variable: EquationVariable) -> SiPrefix {
+        self.prefixes
+            .iter()
+            .find(|(v, _)| *v == variable)
+            .map(|(_, prefix)| *prefix)
+            .unwrap_or_default()
+    }
+
+    /// Returns the value of a given variable in base units
+    pub fn given_value(&self, variable: EquationVariable) -> Result<f64, TrainerError> {
+        self.given_variables
+            .iter()
+            .find(|(v, _)| *v == variable)
+            .map(|(_, val)| *val)
+            .ok_or(TrainerError::MissingVariable(variable))
+    }
+
+    /// Check the answer, user answer is expected to be in the SI prefix requested by the exercise
+    pub fn check_answer(&self, user_answer: f64, policy: Option<GradingPolicy>) -> Option<bool> {
+        let correct_answer = match self.correct_answer {
+            Some(correct_answer) => correct_answer,
+            None => self.solve().ok()?.answer,
+        };
+        let correct_answer = self
+            .prefix_for(self.missing_variable)
+            .from_base(correct_answer);
+        Some(
+            policy
+                .unwrap_or_default()
+                .accepts(user_answer, correct_answer),
+        )
+    }
+
+    /// Fills in the correct answer so that it is sent along with the exercise, only for clients allowed to see it
+    pub fn reveal(&mut self) -> Result<()> {
+        self.correct_answer = Some(self.solve()?.answer);
+        Ok(())
     }
 
     /// Solves the equation and question, returns with precise solution and work needed to achieve the result
@@ -98,13 +251,20 @@ impl EquationExercise {
         let answer: f64;
         let unit: EquationUnit;
 
-        let get_value = |variable: EquationVariable| -> Result<f64, TrainerError> {
-            self.given_variables
-                .iter()
-                .find(|(v, _)| *v == variable)
-                .map(|(_, val)| *val)
-                .ok_or(TrainerError::MissingVariable(variable))
-        };
+        let get_value = |variable: EquationVariable| self.given_value(variable);
+
+        // show the conversion of prefixed values into base units before the actual work
+        for (variable, value) in &self.given_variables {
+            let prefix = self.prefix_for(*variable);
+            if prefix != SiPrefix::Base {
+                steps.push(format!(
+                    "{} = {} = {}",
+                    variable.symbol(),
+                    format_quantity(*value, prefix, variable.unit()),
+                    format_quantity(*value, SiPrefix::Base, variable.unit())
+                ));
+            }
+        }
 
         match self.exercise_type {
             EquationExerciseType::OhmsLaw => match self.missing_variable {
@@ -135,7 +295,13 @@ impl EquationExercise {
                     answer = u / i;
                     steps.push(format!("R = {}", answer));
                 }
-                _ => unreachable!(),
+                missing_variable => {
+                    return Err(TrainerError::InvalidMissingVariable(
+                        self.exercise_type,
+                        missing_variable,
+                    ))
+                    .with_context(|| "Exercise asks for a variable its equation does not solve")
+                }
             },
             EquationExerciseType::Power => match self.missing_variable {
                 EquationVariable::Power => {
@@ -165,7 +331,13 @@ impl EquationExercise {
                     answer = p / u;
                     steps.push(format!("I = {}", answer));
                 }
-                _ => unreachable!(),
+                missing_variable => {
+                    return Err(TrainerError::InvalidMissingVariable(
+                        self.exercise_type,
+                        missing_variable,
+                    ))
+                    .with_context(|| "Exercise asks for a variable its equation does not solve")
+                }
             },
             EquationExerciseType::Combined => match self.missing_variable {
                 EquationVariable::Power => {
@@ -207,10 +379,21 @@ impl EquationExercise {
             },
         }
 
+        let prefix = self.prefix_for(self.missing_variable);
+        if prefix != SiPrefix::Base {
+            steps.push(format!(
+                "{} = {} = {}",
+                self.missing_variable.symbol(),
+                format_quantity(answer, SiPrefix::Base, unit),
+                format_quantity(answer, prefix, unit)
+            ));
+        }
+
         let solution = EquationExerciseSolution {
             steps,
             answer,
             unit,
+            prefix,
         };
 
         if let Some(correct_answer) = self.correct_answer {
@@ -226,6 +409,70 @@ impl EquationExercise {
     }
 }
 
+/// How many times the builder tries to generate a usable exercise before giving up, the hardest levels need thousands
+const MAX_ATTEMPTS: usize = 10_000;
+
+/// How many times a single value is drawn before giving up, when some values in its range round to zero
+const MAX_DRAWS: usize = 100;
+
+/// Voltage, current, resistance and power of a circuit with a single resistor
+#[derive(Copy, Clone, Debug)]
+struct Circuit {
+    voltage: f64,
+    current: f64,
+    resistance: f64,
+    power: f64,
+}
+
+impl Circuit {
+    /// Solves every quantity of the circuit from two different known quantities with the same formulas as solve()
+    fn from_pair(a: (EquationVariable, f64), b: (EquationVariable, f64)) -> Circuit {
+        // indexed in the declaration order of EquationVariable: voltage, current, resistance, power
+        let mut known = [None; 4];
+        for (variable, value) in [a, b] {
+            known[variable as usize] = Some(value);
+        }
+        let (voltage, current, resistance, power) = match known {
+            [Some(u), Some(i), None, None] => (u, i, u / i, u * i),
+            [Some(u), None, Some(r), None] => (u, u / r, r, (u * u) / r),
+            [Some(u), None, None, Some(p)] => (u, p / u, (u * u) / p, p),
+            [None, Some(i), Some(r), None] => (r * i, i, r, i * i * r),
+            [None, Some(i), None, Some(p)] => (p / i, i, p / (i * i), p),
+            [None, None, Some(r), Some(p)] => ((p * r).sqrt(), (p / r).sqrt(), r, p),
+            _ => unreachable!("a circuit is solved from two different quantities"),
+        };
+        Circuit {
+            voltage,
+            current,
+            resistance,
+            power,
+        }
+    }
+
+    /// Returns the value of the quantity
+    fn get(&self, variable: EquationVariable) -> f64 {
+        match variable {
+            EquationVariable::Voltage => self.voltage,
+            EquationVariable::Current => self.current,
+            EquationVariable::Resistance => self.resistance,
+            EquationVariable::Power => self.power,
+        }
+    }
+}
+
+/// Checks that a range can produce positive values for the variable
+fn check_range(variable: EquationVariable, min: f64, max: f64) -> Result<(f64, f64)> {
+    if min > max {
+        return Err(TrainerError::MinLargerThanMax)
+            .with_context(|| format!("min: {}, max: {}", min, max));
+    }
+    if !min.is_finite() || !max.is_finite() || min < 0.0 || max <= 0.0 {
+        return Err(TrainerError::UnsatisfiableRange(variable))
+            .with_context(|| format!("min: {}, max: {} must be positive", min, max));
+    }
+    Ok((min, max))
+}
+
 /// Builder pattern for creating an Exercise
 #[derive(Debug)]
 pub struct EquationExerciseBuilder {
@@ -234,7 +481,13 @@ pub struct EquationExerciseBuilder {
     current_range: (f64, f64),
     resistance_range: (f64, f64),
     power_range: (f64, f64),
-    rng: ThreadRng,
+    use_prefixes: bool,
+    given_decimals: Option<u32>,
+    answer_decimals: Option<u32>,
+    consistent: bool,
+    exercise_types: Vec<EquationExerciseType>,
+    missing_variable: Option<EquationVariable>,
+    rng: ChaCha8Rng,
 }
 
 impl Default for EquationExerciseBuilder {
@@ -245,7 +498,17 @@ impl Default for EquationExerciseBuilder {
             current_range: (0.1, 10.0),
             resistance_range: (1.0, 1000.0),
             power_range: (1.0, 2400.0),
-            rng: rand::thread_rng(),
+            use_prefixes: false,
+            given_decimals: None,
+            answer_decimals: None,
+            consistent: false,
+            exercise_types: vec![
+                EquationExerciseType::OhmsLaw,
+                EquationExerciseType::Power,
+                EquationExerciseType::Combined,
+            ],
+            missing_variable: None,
+            rng: ChaCha8Rng::from_entropy(),
         }
     }
 }
@@ -258,212 +521,376 @@ impl EquationExerciseBuilder {
 
     /// Allows alteration of voltage range min and max values
     pub fn set_voltage_range(mut self, min: f64, max: f64) -> Result<Self> {
-        if min > max {
-            return Err(TrainerError::MinLargerThanMax)
-                .with_context(|| format!("min: {}, max: {}", min, max));
-        }
-        self.voltage_range = (min, max);
+        self.voltage_range = check_range(EquationVariable::Voltage, min, max)?;
         Ok(self)
     }
 
     /// Allows alteration of current range min and max values
     pub fn set_current_range(mut self, min: f64, max: f64) -> Result<Self> {
-        if min > max {
-            return Err(TrainerError::MinLargerThanMax)
-                .with_context(|| format!("min: {}, max: {}", min, max));
-        }
-        self.current_range = (min, max);
+        self.current_range = check_range(EquationVariable::Current, min, max)?;
         Ok(self)
     }
 
     /// Allows alteration of resistance range min and max values
     pub fn set_resistance_range(mut self, min: f64, max: f64) -> Result<Self> {
-        if min > max {
-            return Err(TrainerError::MinLargerThanMax)
-                .with_context(|| format!("min: {}, max: {}", min, max));
-        }
-        self.resistance_range = (min, max);
+        self.resistance_range = check_range(EquationVariable::Resistance, min, max)?;
         Ok(self)
     }
 
     /// Allows alteration of power range min and max values
     pub fn set_power_range(mut self, min: f64, max: f64) -> Result<Self> {
-        if min > max {
-            return Err(TrainerError::MinLargerThanMax)
-                .with_context(|| format!("min: {}, max: {}", min, max));
-        }
-        self.power_range = (min, max);
+        self.power_range = check_range(EquationVariable::Power, min, max)?;
         Ok(self)
     }
 
+    /// Allows alteration of the range of any variable
+    pub fn set_range(self, variable: EquationVariable, min: f64, max: f64) -> Result<Self> {
+        match variable {
+            EquationVariable::Voltage => self.set_voltage_range(min, max),
+            EquationVariable::Current => self.set_current_range(min, max),
+            EquationVariable::Resistance => self.set_resistance_range(min, max),
+            EquationVariable::Power => self.set_power_range(min, max),
+        }
+    }
+
     /// Alter type of the exercise
     pub fn set_type(mut self, new_type: EquationExerciseType) -> Self {
         self.exercise.exercise_type = new_type;
         self
     }
 
-    /// Builds and returns an exercise based on the settings in the builder and proto Exercise within it
-    pub fn build(mut self) -> EquationExercise {
-        let voltage = self
-            .rng
-            .gen_range::<f64, _>(self.voltage_range.0..self.voltage_range.1)
-            .round();
-        let current = self
-            .rng
-            .gen_range::<f64, _>(self.current_range.0..self.current_range.1)
-            .round();
-        let resistance = self
-            .rng
-            .gen_range::<f64, _>(self.resistance_range.0..self.resistance_range.1)
-            .round();
-        let power = self
-            .rng
-            .gen_range::<f64, _>(self.power_range.0..self.power_range.1)
-            .round();
-
-        match self.exercise.exercise_type {
-            EquationExerciseType::OhmsLaw => {
-                let missing_variable = [
-                    EquationVariable::Voltage,
-                    EquationVariable::Current,
-                    EquationVariable::Resistance,
-                ]
-                .choose(&mut self.rng)
-                .unwrap();
-                let mut given_variables = Vec::new();
+    /// Alter the types build_with_random_exercisetype() picks from
+    pub fn set_exercise_types(mut self, exercise_types: Vec<EquationExerciseType>) -> Self {
+        self.exercise_types = exercise_types;
+        self
+    }
 
-                let _correct_answer = match missing_variable {
-                    EquationVariable::Voltage => {
-                        given_variables.push((EquationVariable::Resistance, resistance));
-                        given_variables.push((EquationVariable::Current, current));
-                        resistance * current
-                    }
-                    EquationVariable::Current => {
-                        given_variables.push((EquationVariable::Voltage, voltage));
-                        given_variables.push((EquationVariable::Resistance, resistance));
-                        voltage / resistance
-                    }
-                    EquationVariable::Resistance => {
-                        given_variables.push((EquationVariable::Voltage, voltage));
-                        given_variables.push((EquationVariable::Current, current));
-                        voltage / current
-                    }
-                    _ => unreachable!(),
-                };
-                self.exercise.missing_variable = *missing_variable;
-                self.exercise.given_variables = given_variables;
-                #[cfg(debug_assertions)]
-                {
-                    self.exercise.correct_answer = Some(_correct_answer);
-                }
+    /// Always ask for this variable instead of a random one, None asks for any variable
+    pub fn set_missing_variable(mut self, missing_variable: Option<EquationVariable>) -> Self {
+        self.missing_variable = missing_variable;
+        self
+    }
+
+    /// Present values with SI prefixes (4.7 kΩ, 2 mA) and request the answer in a prefix instead of whole base units
+    pub fn set_prefixes(mut self, use_prefixes: bool) -> Self {
+        self.use_prefixes = use_prefixes;
+        self
+    }
+
+    /// Amount of decimals given values have, in the prefix they are presented in
+    pub fn set_given_decimals(mut self, decimals: u32) -> Self {
+        self.given_decimals = Some(decimals);
+        self
+    }
+
+    /// Generate "nice" answers with at most this many decimals by generating the exercise backwards from the answer, None allows any answer
+    pub fn set_answer_decimals(mut self, decimals: Option<u32>) -> Self {
+        self.answer_decimals = decimals;
+        self
+    }
+
+    /// Require every quantity of the circuit to stay within its range, not only the drawn ones, so that e.g. 1 V and 2400 W never asks for a milliohm resistor
+    pub fn set_consistent(mut self, consistent: bool) -> Self {
+        self.consistent = consistent;
+        self
+    }
+
+    /// Seeds the random number generator so that the same seed and settings always build the same exercises
+    pub fn set_seed(mut self, seed: u64) -> Self {
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+        self
+    }
+
+    /// Applies the ranges, decimals, prefixes and exercise type mix of the difficulty level
+    pub fn set_difficulty(mut self, difficulty: Difficulty) -> Self {
+        self.voltage_range = difficulty.range(EquationVariable::Voltage);
+        self.current_range = difficulty.range(EquationVariable::Current);
+        self.resistance_range = difficulty.range(EquationVariable::Resistance);
+        self.power_range = difficulty.range(EquationVariable::Power);
+        self.use_prefixes = difficulty.use_prefixes();
+        self.given_decimals = Some(difficulty.given_decimals());
+        self.answer_decimals = difficulty.answer_decimals();
+        self.consistent = true;
+        self.exercise_types = difficulty.exercise_types().to_vec();
+        self
+    }
+
+    /// Returns the configured range of the variable
+    pub fn range(&self, variable: EquationVariable) -> (f64, f64) {
+        match variable {
+            EquationVariable::Voltage => self.voltage_range,
+            EquationVariable::Current => self.current_range,
+            EquationVariable::Resistance => self.resistance_range,
+            EquationVariable::Power => self.power_range,
+        }
+    }
+
+    /// Rounds a value in the prefix that suits it, returning None if it rounds to zero
+    fn present(
+        &self,
+        variable: EquationVariable,
+        value: f64,
+        decimals: u32,
+    ) -> Option<(f64, SiPrefix)> {
+        let prefix = if self.use_prefixes {
+            SiPrefix::best_for_variable(variable, value)
+        } else {
+            SiPrefix::Base
+        };
+        let value = prefix.to_base(round_to(prefix.from_base(value), decimals));
+        // a value that rounds to zero would ask the user to divide by zero
+        (value != 0.0).then_some((value, prefix))
+    }
+
+    /// Decimals used for given values unless configured, prefixed values get one decimal like 4.7 kΩ
+    fn given_decimals(&self) -> u32 {
+        self.given_decimals
+            .unwrap_or(if self.use_prefixes { 1 } else { 0 })
+    }
+
+    /// Checks that every quantity of the circuit is within its configured range
+    fn is_plausible(&self, circuit: &Circuit) -> bool {
+        [
+            EquationVariable::Voltage,
+            EquationVariable::Current,
+            EquationVariable::Resistance,
+            EquationVariable::Power,
+        ]
+        .into_iter()
+        .all(|variable| {
+            let (min, max) = self.range(variable);
+            let value = circuit.get(variable);
+            // leave room for floating point error in values derived exactly at the limits
+            let tolerance = max.abs() * 1e-9;
+            value >= min - tolerance && value <= max + tolerance
+        })
+    }
+
+    /// Returns a quantity that can not fall within its range in any circuit with the givens from their ranges
+    fn unreachable(&self, givens: [EquationVariable; 2]) -> Option<EquationVariable> {
+        let (first, second) = (self.range(givens[0]), self.range(givens[1]));
+        // every quantity is monotonic in both givens, so its extremes are found in the corners of the ranges
+        let corners = [first.0, first.1].map(|a| {
+            [second.0, second.1].map(|b| Circuit::from_pair((givens[0], a), (givens[1], b)))
+        });
+        [
+            EquationVariable::Voltage,
+            EquationVariable::Current,
+            EquationVariable::Resistance,
+            EquationVariable::Power,
+        ]
+        .into_iter()
+        .find(|variable| {
+            let values = corners
+                .iter()
+                .flatten()
+                .map(|circuit| circuit.get(*variable));
+            let lowest = values.clone().fold(f64::INFINITY, f64::min);
+            let highest = values.fold(f64::NEG_INFINITY, f64::max);
+            let (min, max) = self.range(*variable);
+            let tolerance = max.abs() * 1e-9;
+            highest < min - tolerance || lowest > max + tolerance
+        })
+    }
+
+    /// Draws a value for the variable from its range, rounded either in base units or in the prefix that suits it
+    fn draw(&mut self, variable: EquationVariable, decimals: u32) -> Result<(f64, SiPrefix)> {
+        let range = self.range(variable);
+        // smaller values round to zero as well when the largest one does
+        if self.present(variable, range.1, decimals).is_none() {
+            return Err(TrainerError::UnsatisfiableRange(variable)).with_context(|| {
+                format!("min: {}, max: {} always rounds to zero", range.0, range.1)
+            });
+        }
+        for _ in 0..MAX_DRAWS {
+            let value = self.rng.gen_range::<f64, _>(range.0..=range.1);
+            if let Some(presented) = self.present(variable, value, decimals) {
+                return Ok(presented);
             }
-            EquationExerciseType::Power => {
-                let missing_variable = [
-                    EquationVariable::Power,
-                    EquationVariable::Voltage,
-                    EquationVariable::Current,
-                ]
-                .choose(&mut self.rng)
-                .unwrap();
-                let mut given_variables = Vec::new();
-                let _correct_answer = match missing_variable {
-                    EquationVariable::Power => {
-                        given_variables.push((EquationVariable::Voltage, voltage));
-                        given_variables.push((EquationVariable::Current, current));
-                        voltage * current
-                    }
-                    EquationVariable::Voltage => {
-                        given_variables.push((EquationVariable::Power, power));
-                        given_variables.push((EquationVariable::Current, current));
-                        power / current
-                    }
-                    EquationVariable::Current => {
-                        given_variables.push((EquationVariable::Power, power));
-                        given_variables.push((EquationVariable::Voltage, voltage));
-                        power / voltage
-                    }
-                    _ => unreachable!(),
-                };
-                self.exercise.missing_variable = *missing_variable;
-                self.exercise.given_variables = given_variables;
-                #[cfg(debug_assertions)]
-                {
-                    self.exercise.correct_answer = Some(_correct_answer);
-                }
+        }
+        Err(TrainerError::UnsatisfiableRange(variable))
+            .with_context(|| format!("min: {}, max: {} always rounds to zero", range.0, range.1))
+    }
+
+    /// Builds and returns an exercise based on the settings in the builder and proto Exercise within it, calling it again builds the next exercise of the same set
+    pub fn build(&mut self) -> Result<EquationExercise> {
+        let exercise_type = self.exercise.exercise_type;
+        if let Some(missing_variable) = self.missing_variable {
+            if !exercise_type
+                .missing_variables()
+                .contains(&missing_variable)
+            {
+                return Err(TrainerError::InvalidMissingVariable(
+                    exercise_type,
+                    missing_variable,
+                ))
+                .with_context(|| "Requested missing variable does not suit the exercise type");
             }
-            EquationExerciseType::Combined => {
-                let selection = [
-                    (
-                        EquationVariable::Voltage,
-                        EquationVariable::Resistance,
-                        EquationVariable::Power,
-                    ),
-                    (
-                        EquationVariable::Power,
-                        EquationVariable::Resistance,
-                        EquationVariable::Current,
-                    ),
-                    (
-                        EquationVariable::Power,
-                        EquationVariable::Voltage,
-                        EquationVariable::Resistance,
-                    ),
-                ]
-                .choose(&mut self.rng)
-                .unwrap();
-                let mut given_variables = Vec::new();
-                let _correct_answer = match selection {
-                    (
-                        EquationVariable::Voltage,
-                        EquationVariable::Resistance,
-                        EquationVariable::Power,
-                    ) => {
-                        given_variables.push((EquationVariable::Voltage, voltage));
-                        given_variables.push((EquationVariable::Resistance, resistance));
-                        (voltage * voltage) / resistance
-                    }
-                    (
-                        EquationVariable::Power,
-                        EquationVariable::Resistance,
-                        EquationVariable::Current,
-                    ) => {
-                        given_variables.push((EquationVariable::Power, power));
-                        given_variables.push((EquationVariable::Resistance, resistance));
-                        (power / resistance).sqrt()
-                    }
-                    (
-                        EquationVariable::Power,
-                        EquationVariable::Voltage,
-                        EquationVariable::Resistance,
-                    ) => {
-                        given_variables.push((EquationVariable::Power, power));
-                        given_variables.push((EquationVariable::Voltage, voltage));
-                        (voltage * voltage) / power
-                    }
-                    _ => unreachable!(),
+        }
+        for _ in 0..MAX_ATTEMPTS {
+            let Some(correct_answer) = self.generate()? else {
+                continue;
+            };
+            if !correct_answer.is_finite() || correct_answer == 0.0 {
+                continue;
+            }
+            self.exercise.id = self.exercise.fingerprint();
+            return Ok(self.exercise.clone()); // clone the protype exercise so that it can be re-used when calling build() again
+        }
+        Err(TrainerError::UnsatisfiableRange(
+            self.exercise.missing_variable,
+        ))
+        .with_context(|| "Unable to generate an exercise with a usable answer".to_string())
+    }
+
+    /// Fills the proto Exercise with freshly drawn values and returns the correct answer, or None if the draw was not usable
+    fn generate(&mut self) -> Result<Option<f64>> {
+        let exercise_type = self.exercise.exercise_type;
+        let candidates = match self.missing_variable {
+            Some(missing_variable) => vec![missing_variable],
+            None => exercise_type.missing_variables().to_vec(),
+        };
+        // leave out the variables whose givens can never make a circuit within the ranges
+        let reachable: Vec<EquationVariable> = candidates
+            .iter()
+            .copied()
+            .filter(|missing_variable| {
+                !self.consistent
+                    || self
+                        .unreachable(exercise_type.given_variables(*missing_variable).unwrap())
+                        .is_none()
+            })
+            .collect();
+        let Some(&missing_variable) = reachable.choose(&mut self.rng) else {
+            let givens = exercise_type.given_variables(candidates[0]).unwrap();
+            let variable = self.unreachable(givens).unwrap();
+            return Err(TrainerError::UnsatisfiableRange(variable))
+                .with_context(|| "The ranges can never make a consistent circuit".to_string());
+        };
+        let givens = exercise_type.given_variables(missing_variable).unwrap();
+        let given_decimals = self.given_decimals();
+
+        let (given, answer_prefix) = match self.answer_decimals {
+            None => {
+                let first = self.draw(givens[0], given_decimals)?;
+                let second = self.draw(givens[1], given_decimals)?;
+                let circuit = Circuit::from_pair((givens[0], first.0), (givens[1], second.0));
+                let answer = circuit.get(missing_variable);
+                let answer_prefix = SiPrefix::best_for_variable(missing_variable, answer);
+                ([first, second], answer_prefix)
+            }
+            Some(answer_decimals) => {
+                // generate backwards: draw the answer and one of the givens, then derive the other given from them
+                // answers with fewer decimals than allowed are nice as well and much more likely to work out
+                let answer_decimals = self.rng.gen_range(0..=answer_decimals);
+                let (answer, answer_prefix) = self.draw(missing_variable, answer_decimals)?;
+                let drawn_index = self.rng.gen_range(0..2);
+                let derived_index = 1 - drawn_index;
+                let drawn = self.draw(givens[drawn_index], given_decimals)?;
+                let circuit =
+                    Circuit::from_pair((missing_variable, answer), (givens[drawn_index], drawn.0));
+                let derived_value = circuit.get(givens[derived_index]);
+                let Some(derived) =
+                    self.present(givens[derived_index], derived_value, given_decimals)
+                else {
+                    return Ok(None);
                 };
-                self.exercise.missing_variable = selection.2;
-                self.exercise.given_variables = given_variables;
-                #[cfg(debug_assertions)]
-                {
-                    self.exercise.correct_answer = Some(_correct_answer);
+                // the derived value must not need more decimals than the given values are allowed to have
+                if (derived.0 - derived_value).abs() > derived_value.abs() * 1e-9 {
+                    return Ok(None);
                 }
+                // nor fall outside the range of its variable, as the drawn values are
+                let (min, max) = self.range(givens[derived_index]);
+                if derived.0 < min || derived.0 > max {
+                    return Ok(None);
+                }
+                let mut given = [drawn, drawn];
+                given[derived_index] = derived;
+                (given, answer_prefix)
             }
+        };
+
+        let circuit = Circuit::from_pair((givens[0], given[0].0), (givens[1], given[1].0));
+        if self.consistent && !self.is_plausible(&circuit) {
+            return Ok(None);
         }
-        self.exercise.clone() // clone the protype exercise so that it can be re-used when calling build() again
+        self.exercise.missing_variable = missing_variable;
+        self.exercise.given_variables = vec![(givens[0], given[0].0), (givens[1], given[1].0)];
+        self.exercise.prefixes.clear();
+        if self.use_prefixes {
+            self.exercise.prefixes = vec![
+                (givens[0], given[0].1),
+                (givens[1], given[1].1),
+                (missing_variable, answer_prefix),
+            ];
+        }
+
+        Ok(Some(circuit.get(missing_variable)))
     }
 
-    /// Builds a new exercise with randomized ExerciseType
-    pub fn build_with_random_exercisetype(mut self) -> EquationExercise {
-        let exercise_types = [
-            EquationExerciseType::OhmsLaw,
-            EquationExerciseType::Power,
-            EquationExerciseType::Combined,
-        ];
-        let exercise_type = exercise_types.choose(&mut self.rng).unwrap();
+    /// Builds a new exercise with randomized ExerciseType, limited to the configured types that can ask for the missing variable
+    pub fn build_with_random_exercisetype(&mut self) -> Result<EquationExercise> {
+        let candidates: Vec<EquationExerciseType> = self
+            .exercise_types
+            .iter()
+            .copied()
+            .filter(|exercise_type| match self.missing_variable {
+                Some(missing_variable) => exercise_type
+                    .missing_variables()
+                    .contains(&missing_variable),
+                None => true,
+            })
+            .collect();
+        let Some(exercise_type) = candidates.choose(&mut self.rng) else {
+            return Err(TrainerError::NoExerciseType).with_context(|| {
+                format!(
+                    "None of {:?} can ask for {:?}",
+                    self.exercise_types, self.missing_variable
+                )
+            });
+        };
         self.exercise.exercise_type = *exercise_type;
         self.build()
     }
+
+    /// Builds a new exercise whose type and missing variable are drawn with the given weights, e.g. from a spaced repetition scheduler
+    pub fn build_weighted(
+        &mut self,
+        weight: impl Fn(EquationExerciseType, EquationVariable) -> u32,
+    ) -> Result<EquationExercise> {
+        let candidates: Vec<(EquationExerciseType, EquationVariable)> = self
+            .exercise_types
+            .iter()
+            .flat_map(|exercise_type| {
+                exercise_type
+                    .missing_variables()
+                    .iter()
+                    .map(move |missing_variable| (*exercise_type, *missing_variable))
+            })
+            .filter(|(_, missing_variable)| {
+                self.missing_variable
+                    .is_none_or(|requested| requested == *missing_variable)
+            })
+            .collect();
+        let Ok(&(exercise_type, missing_variable)) = candidates
+            .choose_weighted(&mut self.rng, |(exercise_type, missing_variable)| {
+                weight(*exercise_type, *missing_variable)
+            })
+        else {
+            return Err(TrainerError::NoExerciseType).with_context(|| {
+                format!(
+                    "None of {:?} can ask for {:?}",
+                    self.exercise_types, self.missing_variable
+                )
+            });
+        };
+        self.exercise.exercise_type = exercise_type;
+        // the drawn variable is only asked in this exercise, the next one is drawn again
+        let requested = self.missing_variable.replace(missing_variable);
+        let exercise = self.build();
+        self.missing_variable = requested;
+        exercise
+    }
 }
 
 #[cfg(test)]
@@ -475,7 +902,8 @@ mod tests {
     fn test_ohms_law_voltage() {
         let exercise = EquationExerciseBuilder::new()
             .set_type(EquationExerciseType::OhmsLaw)
-            .build();
+            .build()
+            .unwrap();
 
         if exercise.missing_variable == EquationVariable::Voltage {
             let solution = exercise.solve().unwrap();
@@ -500,7 +928,8 @@ mod tests {
     fn test_ohms_law_current() {
         let exercise = EquationExerciseBuilder::new()
             .set_type(EquationExerciseType::OhmsLaw)
-            .build();
+            .build()
+            .unwrap();
 
         if exercise.missing_variable == EquationVariable::Current {
             let solution = exercise.solve().unwrap();
@@ -525,7 +954,8 @@ mod tests {
     fn test_ohms_law_resistance() {
         let exercise = EquationExerciseBuilder::new()
             .set_type(EquationExerciseType::OhmsLaw)
-            .build();
+            .build()
+            .unwrap();
 
         if exercise.missing_variable == EquationVariable::Resistance {
             let solution = exercise.solve().unwrap();
@@ -550,7 +980,8 @@ mod tests {
     fn test_power_power() {
         let exercise = EquationExerciseBuilder::new()
             .set_type(EquationExerciseType::Power)
-            .build();
+            .build()
+            .unwrap();
 
         if exercise.missing_variable == EquationVariable::Power {
             let solution = exercise.solve().unwrap();
@@ -575,7 +1006,8 @@ mod tests {
     fn test_power_voltage() {
         let exercise = EquationExerciseBuilder::new()
             .set_type(EquationExerciseType::Power)
-            .build();
+            .build()
+            .unwrap();
 
         if exercise.missing_variable == EquationVariable::Voltage {
             let solution = exercise.solve().unwrap();
@@ -600,7 +1032,8 @@ mod tests {
     fn test_power_current() {
         let exercise = EquationExerciseBuilder::new()
             .set_type(EquationExerciseType::Power)
-            .build();
+            .build()
+            .unwrap();
 
         if exercise.missing_variable == EquationVariable::Current {
             let solution = exercise.solve().unwrap();
@@ -625,7 +1058,8 @@ mod tests {
     fn test_combined_power() {
         let exercise = EquationExerciseBuilder::new()
             .set_type(EquationExerciseType::Combined)
-            .build();
+            .build()
+            .unwrap();
 
         if exercise.missing_variable == EquationVariable::Power {
             let solution = exercise.solve().unwrap();
@@ -654,7 +1088,8 @@ mod tests {
     fn test_combined_current() {
         let exercise = EquationExerciseBuilder::new()
             .set_type(EquationExerciseType::Combined)
-            .build();
+            .build()
+            .unwrap();
 
         if exercise.missing_variable == EquationVariable::Current {
             let solution = exercise.solve().unwrap();
@@ -679,7 +1114,8 @@ mod tests {
     fn test_combined_voltage() {
         let exercise = EquationExerciseBuilder::new()
             .set_type(EquationExerciseType::Combined)
-            .build();
+            .build()
+            .unwrap();
 
         if exercise.missing_variable == EquationVariable::Voltage {
             let solution = exercise.solve().unwrap();
@@ -704,7 +1140,8 @@ mod tests {
     fn test_combined_resistance() {
         let exercise = EquationExerciseBuilder::new()
             .set_type(EquationExerciseType::Combined)
-            .build();
+            .build()
+            .unwrap();
 
         if exercise.missing_variable == EquationVariable::Resistance {
             let solution = exercise.solve().unwrap();
@@ -728,12 +1165,15 @@ mod tests {
     #[test]
     fn test_check_answer_correct() {
         let exercise = EquationExercise {
+            id: String::new(),
+            token: None,
             exercise_type: EquationExerciseType::OhmsLaw,
             missing_variable: EquationVariable::Resistance,
             given_variables: vec![
                 (EquationVariable::Voltage, 12.0),
                 (EquationVariable::Current, 2.0),
             ],
+            prefixes: vec![],
             correct_answer: Some(6.0),
         };
         assert_eq!(exercise.check_answer(6.0, None), Some(true));
@@ -742,12 +1182,15 @@ mod tests {
     #[test]
     fn test_check_answer_incorrect() {
         let exercise = EquationExercise {
+            id: String::new(),
+            token: None,
             exercise_type: EquationExerciseType::OhmsLaw,
             missing_variable: EquationVariable::Resistance,
             given_variables: vec![
                 (EquationVariable::Voltage, 12.0),
                 (EquationVariable::Current, 2.0),
             ],
+            prefixes: vec![],
             correct_answer: Some(6.0),
         };
         assert_eq!(exercise.check_answer(5.0, None), Some(false));
@@ -756,19 +1199,29 @@ mod tests {
     #[test]
     fn test_check_answer_precision() {
         let exercise = EquationExercise {
+            id: String::new(),
+            token: None,
             exercise_type: EquationExerciseType::OhmsLaw,
             missing_variable: EquationVariable::Resistance,
             given_variables: vec![
                 (EquationVariable::Voltage, 12.0),
                 (EquationVariable::Current, 2.0),
             ],
+            prefixes: vec![],
             correct_answer: Some(6.0),
         };
-        assert_eq!(exercise.check_answer(6.05, Some(0.1)), Some(true));
-        assert_eq!(exercise.check_answer(6.15, Some(0.1)), Some(false));
+        assert_eq!(
+            exercise.check_answer(6.05, Some(GradingPolicy::Absolute(0.1))),
+            Some(true)
+        );
+        assert_eq!(
+            exercise.check_answer(6.15, Some(GradingPolicy::Absolute(0.1))),
+            Some(false)
+        );
     }
 
     #[test]
+    #[allow(clippy::manual_range_contains)]
     fn test_build_exercise() {
         let exercise = EquationExerciseBuilder::new()
             .set_voltage_range(10.0, 20.0)
@@ -780,7 +1233,8 @@ mod tests {
             .set_power_range(50.0, 100.0)
             .unwrap()
             .set_type(EquationExerciseType::OhmsLaw)
-            .build();
+            .build()
+            .unwrap();
 
         assert!(exercise.exercise_type == EquationExerciseType::OhmsLaw);
 
@@ -796,7 +1250,9 @@ mod tests {
 
     #[test]
     fn test_build_exercise_random_type() {
-        let exercise = EquationExerciseBuilder::new().build_with_random_exercisetype();
+        let exercise = EquationExerciseBuilder::new()
+            .build_with_random_exercisetype()
+            .unwrap();
         assert!(
             exercise.exercise_type == EquationExerciseType::OhmsLaw
                 || exercise.exercise_type == EquationExerciseType::Power
@@ -819,4 +1275,460 @@ mod tests {
             .set_power_range(100.0, 50.0)
             .is_err());
     }
+
+    #[test]
+    fn test_build_exercise_with_prefixes() {
+        let exercise = EquationExerciseBuilder::new()
+            .set_voltage_range(1.0, 10.0)
+            .unwrap()
+            .set_current_range(0.001, 0.009)
+            .unwrap()
+            .set_resistance_range(1000.0, 9000.0)
+            .unwrap()
+            .set_prefixes(true)
+            .set_type(EquationExerciseType::OhmsLaw)
+            .build()
+            .unwrap();
+
+        for &(variable, value) in &exercise.given_variables {
+            match variable {
+                EquationVariable::Current => {
+                    assert_eq!(exercise.prefix_for(variable), SiPrefix::Milli);
+                    assert!((0.001..=0.009).contains(&value));
+                }
+                EquationVariable::Resistance => {
+                    assert_eq!(exercise.prefix_for(variable), SiPrefix::Kilo);
+                    assert!((1000.0..=9000.0).contains(&value));
+                }
+                _ => assert_eq!(exercise.prefix_for(variable), SiPrefix::Base),
+            }
+        }
+        assert_eq!(exercise.prefixes.len(), 3);
+    }
+
+    #[test]
+    fn test_solve_with_prefixes() {
+        let exercise = EquationExercise {
+            id: String::new(),
+            token: None,
+            exercise_type: EquationExerciseType::OhmsLaw,
+            missing_variable: EquationVariable::Voltage,
+            given_variables: vec![
+                (EquationVariable::Resistance, 4700.0),
+                (EquationVariable::Current, 0.002),
+            ],
+            prefixes: vec![
+                (EquationVariable::Resistance, SiPrefix::Kilo),
+                (EquationVariable::Current, SiPrefix::Milli),
+                (EquationVariable::Voltage, SiPrefix::Milli),
+            ],
+            correct_answer: None,
+        };
+        let solution = exercise.solve().unwrap();
+        assert_relative_eq!(solution.answer, 9.4, epsilon = 1e-9);
+        assert_relative_eq!(solution.prefixed_answer(), 9400.0, epsilon = 1e-6);
+        assert_eq!(solution.prefix, SiPrefix::Milli);
+        assert_eq!(solution.steps[0], "R = 4.7 kΩ = 4700 Ω");
+        assert_eq!(solution.steps[1], "I = 2 mA = 0.002 A");
+        assert_eq!(solution.steps.last().unwrap(), "U = 9.4 V = 9400 mV");
+    }
+
+    #[test]
+    fn test_check_answer_in_prefix() {
+        let exercise = EquationExercise {
+            id: String::new(),
+            token: None,
+            exercise_type: EquationExerciseType::OhmsLaw,
+            missing_variable: EquationVariable::Current,
+            given_variables: vec![
+                (EquationVariable::Voltage, 12.0),
+                (EquationVariable::Resistance, 4000.0),
+            ],
+            prefixes: vec![(EquationVariable::Current, SiPrefix::Milli)],
+            correct_answer: Some(0.003),
+        };
+        assert_eq!(exercise.check_answer(3.0, None), Some(true));
+        assert_eq!(exercise.check_answer(0.003, None), Some(false));
+    }
+
+    #[test]
+    fn test_build_never_produces_degenerate_values() {
+        for exercise_type in [
+            EquationExerciseType::OhmsLaw,
+            EquationExerciseType::Power,
+            EquationExerciseType::Combined,
+        ] {
+            for _ in 0..200 {
+                let exercise = EquationExerciseBuilder::new()
+                    .set_type(exercise_type)
+                    .build()
+                    .unwrap();
+                for &(_, value) in &exercise.given_variables {
+                    assert!(value != 0.0);
+                }
+                let answer = exercise.solve().unwrap().answer;
+                assert!(answer.is_finite() && answer != 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_unsatisfiable_ranges() {
+        // current is a given value when the voltage is asked
+        let result = EquationExerciseBuilder::new()
+            .set_current_range(0.1, 0.4)
+            .unwrap()
+            .set_type(EquationExerciseType::OhmsLaw)
+            .set_missing_variable(Some(EquationVariable::Voltage))
+            .build();
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<TrainerError>(),
+            Some(TrainerError::UnsatisfiableRange(EquationVariable::Current))
+        ));
+
+        // the same range works when values may use prefixes, as 0.1 A is 100 mA
+        assert!(EquationExerciseBuilder::new()
+            .set_current_range(0.1, 0.4)
+            .unwrap()
+            .set_prefixes(true)
+            .build()
+            .is_ok());
+
+        assert!(EquationExerciseBuilder::new()
+            .set_voltage_range(-10.0, 10.0)
+            .is_err());
+        assert!(EquationExerciseBuilder::new()
+            .set_power_range(0.0, 0.0)
+            .is_err());
+        assert!(EquationExerciseBuilder::new()
+            .set_resistance_range(10.0, 10.0)
+            .is_ok());
+    }
+
+    fn has_decimals(value: f64, decimals: u32) -> bool {
+        (round_to(value, decimals) - value).abs() <= value.abs() * 1e-9
+    }
+
+    #[test]
+    fn test_build_nice_numbers() {
+        for exercise_type in [
+            EquationExerciseType::OhmsLaw,
+            EquationExerciseType::Power,
+            EquationExerciseType::Combined,
+        ] {
+            for answer_decimals in [0, 2] {
+                for _ in 0..50 {
+                    let exercise = EquationExerciseBuilder::new()
+                        .set_type(exercise_type)
+                        .set_given_decimals(0)
+                        .set_answer_decimals(Some(answer_decimals))
+                        .build()
+                        .unwrap();
+                    for &(_, value) in &exercise.given_variables {
+                        assert!(has_decimals(value, 0), "{:?}", exercise);
+                    }
+                    let solution = exercise.solve().unwrap();
+                    assert!(
+                        has_decimals(solution.answer, answer_decimals),
+                        "{:?}",
+                        exercise
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_build_nice_numbers_within_ranges() {
+        for answer_decimals in [0, 2] {
+            let mut builder = EquationExerciseBuilder::new()
+                .set_answer_decimals(Some(answer_decimals))
+                .set_seed(7);
+            for _ in 0..200 {
+                let exercise = builder.build_with_random_exercisetype().unwrap();
+                for &(variable, value) in &exercise.given_variables {
+                    let (min, max) = builder.range(variable);
+                    assert!(value >= min && value <= max, "{:?}", exercise);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_build_given_decimals() {
+        for _ in 0..50 {
+            let exercise = EquationExerciseBuilder::new()
+                .set_given_decimals(2)
+                .build_with_random_exercisetype()
+                .unwrap();
+            for &(_, value) in &exercise.given_variables {
+                assert!(has_decimals(value, 2));
+            }
+        }
+    }
+
+    #[test]
+    fn test_build_nice_numbers_with_prefixes() {
+        for _ in 0..50 {
+            let exercise = EquationExerciseBuilder::new()
+                .set_prefixes(true)
+                .set_answer_decimals(Some(1))
+                .build_with_random_exercisetype()
+                .unwrap();
+            for &(variable, value) in &exercise.given_variables {
+                assert!(has_decimals(
+                    exercise.prefix_for(variable).from_base(value),
+                    1
+                ));
+            }
+            let solution = exercise.solve().unwrap();
+            assert!(
+                has_decimals(solution.prefixed_answer(), 1),
+                "{:?}",
+                exercise
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_consistent() {
+        for exercise_type in [
+            EquationExerciseType::OhmsLaw,
+            EquationExerciseType::Power,
+            EquationExerciseType::Combined,
+        ] {
+            for answer_decimals in [None, Some(1)] {
+                for _ in 0..50 {
+                    let exercise = EquationExerciseBuilder::new()
+                        .set_type(exercise_type)
+                        .set_answer_decimals(answer_decimals)
+                        .set_consistent(true)
+                        .build()
+                        .unwrap();
+                    let given = &exercise.given_variables;
+                    let circuit = Circuit::from_pair(given[0], given[1]);
+                    assert!((1.0..=240.0).contains(&circuit.voltage), "{:?}", exercise);
+                    assert!((0.1..=10.0).contains(&circuit.current), "{:?}", exercise);
+                    assert!(
+                        (1.0..=1000.0).contains(&circuit.resistance),
+                        "{:?}",
+                        exercise
+                    );
+                    assert!((1.0..=2400.0).contains(&circuit.power), "{:?}", exercise);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_build_consistent_impossible_ranges() {
+        // 1 V over at least 1 kΩ can never draw 1 A
+        let result = EquationExerciseBuilder::new()
+            .set_voltage_range(1.0, 1.0)
+            .unwrap()
+            .set_resistance_range(1000.0, 2000.0)
+            .unwrap()
+            .set_current_range(1.0, 2.0)
+            .unwrap()
+            .set_consistent(true)
+            .build_with_random_exercisetype();
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<TrainerError>(),
+            Some(TrainerError::UnsatisfiableRange(_))
+        ));
+
+        // found before drawing, as no circuit with at most 4 Ω and 0.5 A can take 100 W
+        let result = EquationExerciseBuilder::new()
+            .set_resistance_range(1.0, 4.0)
+            .unwrap()
+            .set_current_range(0.1, 0.5)
+            .unwrap()
+            .set_power_range(100.0, 200.0)
+            .unwrap()
+            .set_consistent(true)
+            .set_type(EquationExerciseType::OhmsLaw)
+            .set_missing_variable(Some(EquationVariable::Voltage))
+            .build();
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<TrainerError>(),
+            Some(TrainerError::UnsatisfiableRange(EquationVariable::Power))
+        ));
+    }
+
+    #[test]
+    fn test_build_seeded() {
+        let build_set = |seed: u64| {
+            let mut builder = EquationExerciseBuilder::new()
+                .set_seed(seed)
+                .set_answer_decimals(Some(1));
+            (0..10)
+                .map(|_| builder.build_with_random_exercisetype().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(build_set(42), build_set(42));
+        assert_ne!(build_set(42), build_set(43));
+
+        // every exercise of a set is drawn anew
+        let set = build_set(7);
+        assert!(set.iter().any(|exercise| *exercise != set[0]));
+
+        let build = || {
+            EquationExerciseBuilder::new()
+                .set_seed(1)
+                .set_type(EquationExerciseType::Combined)
+                .build()
+                .unwrap()
+        };
+        assert_eq!(build(), build());
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let exercise = EquationExerciseBuilder::new()
+            .set_prefixes(true)
+            .build_with_random_exercisetype()
+            .unwrap();
+        assert_eq!(exercise.id, exercise.fingerprint());
+        assert_eq!(exercise.id.len(), 16);
+
+        // the order of the given values does not change the problem
+        let mut reordered = exercise.clone();
+        reordered.given_variables.reverse();
+        assert_eq!(reordered.fingerprint(), exercise.fingerprint());
+
+        let mut changed = exercise.clone();
+        changed.given_variables[0].1 *= 2.0;
+        assert_ne!(changed.fingerprint(), exercise.fingerprint());
+
+        let other_prefix = match exercise.prefix_for(exercise.missing_variable) {
+            SiPrefix::Micro => SiPrefix::Milli,
+            _ => SiPrefix::Micro,
+        };
+        let mut requested_in_other_prefix = exercise.clone();
+        requested_in_other_prefix
+            .prefixes
+            .retain(|(variable, _)| *variable != exercise.missing_variable);
+        requested_in_other_prefix
+            .prefixes
+            .push((exercise.missing_variable, other_prefix));
+        assert_ne!(
+            requested_in_other_prefix.fingerprint(),
+            exercise.fingerprint()
+        );
+    }
+
+    #[test]
+    fn test_answer_hidden_until_revealed() {
+        let mut exercise = EquationExerciseBuilder::new()
+            .build_with_random_exercisetype()
+            .unwrap();
+        assert_eq!(exercise.correct_answer, None);
+        assert!(!serde_json::to_string(&exercise)
+            .unwrap()
+            .contains("correct_answer"));
+
+        let answer = exercise.solve().unwrap().answer;
+        assert_eq!(exercise.check_answer(answer, None), Some(true));
+
+        exercise.reveal().unwrap();
+        assert_eq!(exercise.correct_answer, Some(answer));
+        assert!(serde_json::to_string(&exercise)
+            .unwrap()
+            .contains("correct_answer"));
+    }
+
+    #[test]
+    fn test_solve_invalid_missing_variable() {
+        let exercise = EquationExercise {
+            exercise_type: EquationExerciseType::OhmsLaw,
+            missing_variable: EquationVariable::Power,
+            ..EquationExercise::default()
+        };
+        let error = exercise.solve().unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<TrainerError>(),
+            Some(TrainerError::InvalidMissingVariable(
+                EquationExerciseType::OhmsLaw,
+                EquationVariable::Power
+            ))
+        ));
+        assert_eq!(exercise.check_answer(1.0, None), None);
+    }
+
+    #[test]
+    fn test_build_missing_variable() {
+        for _ in 0..20 {
+            let exercise = EquationExerciseBuilder::new()
+                .set_missing_variable(Some(EquationVariable::Resistance))
+                .build_with_random_exercisetype()
+                .unwrap();
+            assert_eq!(exercise.missing_variable, EquationVariable::Resistance);
+            assert_ne!(exercise.exercise_type, EquationExerciseType::Power);
+        }
+
+        let result = EquationExerciseBuilder::new()
+            .set_type(EquationExerciseType::Power)
+            .set_missing_variable(Some(EquationVariable::Resistance))
+            .build();
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<TrainerError>(),
+            Some(TrainerError::InvalidMissingVariable(
+                EquationExerciseType::Power,
+                EquationVariable::Resistance
+            ))
+        ));
+
+        let result = EquationExerciseBuilder::new()
+            .set_exercise_types(vec![EquationExerciseType::Power])
+            .set_missing_variable(Some(EquationVariable::Resistance))
+            .build_with_random_exercisetype();
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<TrainerError>(),
+            Some(TrainerError::NoExerciseType)
+        ));
+    }
+
+    #[test]
+    fn test_build_weighted() {
+        let mut builder = EquationExerciseBuilder::new();
+        for _ in 0..20 {
+            let exercise = builder
+                .build_weighted(|exercise_type, missing_variable| {
+                    (exercise_type == EquationExerciseType::Combined
+                        && missing_variable == EquationVariable::Current) as u32
+                })
+                .unwrap();
+            assert_eq!(exercise.exercise_type, EquationExerciseType::Combined);
+            assert_eq!(exercise.missing_variable, EquationVariable::Current);
+        }
+        // the drawn variable does not stick to the builder
+        let asked: Vec<_> = (0..30)
+            .map(|_| builder.build_with_random_exercisetype().unwrap())
+            .map(|exercise| exercise.missing_variable)
+            .collect();
+        assert!(asked.iter().any(|variable| *variable != asked[0]));
+
+        let mut builder =
+            EquationExerciseBuilder::new().set_missing_variable(Some(EquationVariable::Resistance));
+        let exercise = builder.build_weighted(|_, _| 1).unwrap();
+        assert_eq!(exercise.missing_variable, EquationVariable::Resistance);
+        assert!(matches!(
+            builder
+                .build_weighted(|_, _| 0)
+                .unwrap_err()
+                .downcast_ref::<TrainerError>(),
+            Some(TrainerError::NoExerciseType)
+        ));
+    }
+
+    #[test]
+    fn test_set_range() {
+        let builder = EquationExerciseBuilder::new()
+            .set_range(EquationVariable::Power, 10.0, 20.0)
+            .unwrap();
+        assert_eq!(builder.range(EquationVariable::Power), (10.0, 20.0));
+        assert!(EquationExerciseBuilder::new()
+            .set_range(EquationVariable::Voltage, 20.0, 10.0)
+            .is_err());
+    }
 }
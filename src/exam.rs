@@ -0,0 +1,417 @@
+#![warn(missing_docs)]
+
+//! PUImURI trainer for Ohms law, power equation and their combinations
+//!
+//! Timed exams with a fixed set of exercises, answers locked on submission and results released when the exam closes
+
+use eyre::{Context, Result};
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+
+use crate::answer::ParsedAnswer;
+use crate::equations::EquationExercise;
+use crate::grading::GradingPolicy;
+use crate::scoring::ScoringModel;
+use crate::spec::ExerciseSetSpec;
+use crate::TrainerError;
+
+/// How many students can submit their answers to one exam
+pub const MAX_SUBMISSIONS: usize = 1_000;
+
+/// Settings of an exam as defined by the teacher
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct ExamSpec {
+    /// Exercises of the exam, a random seed is chosen if the set has none
+    #[serde(flatten)]
+    pub set: ExerciseSetSpec,
+    /// Seconds from the creation of the exam until it closes
+    pub time_limit: u64,
+    /// Give every student the exercises in their own order
+    #[serde(default)]
+    pub shuffle: bool,
+}
+
+/// Answer of a student to one exercise of the exam
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ExamAnswer {
+    /// Identifier of the answered exercise
+    pub exercise_id: String,
+    /// Answer as typed by the student
+    pub answer: String,
+}
+
+/// Graded answer, only shown once the exam has closed
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ExamResult {
+    /// Identifier of the exercise
+    pub exercise_id: String,
+    /// Answer of the student, None if the exercise was left unanswered
+    pub answer: Option<String>,
+    /// Whether the answer was fully correct
+    pub correct: bool,
+    /// Partial credit between 0 and 1
+    pub score: f64,
+}
+
+/// Locked answers of one student
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Submission {
+    /// Name of the student
+    pub student: String,
+    /// When the answers were submitted, in seconds since the Unix epoch
+    pub submitted_at: u64,
+    /// Sum of the partial credit of every exercise
+    pub score: f64,
+    /// Results in the order of the exercises of the exam
+    pub results: Vec<ExamResult>,
+}
+
+/// Grades the answers to a fixed set of exercises, unanswered exercises earn nothing
+///
+/// A typo rejects all answers so that they can be fixed before they are locked.
+pub fn grade_answers(
+    exercises: &[EquationExercise],
+    answers: &[ExamAnswer],
+    policy: GradingPolicy,
+    scoring: &ScoringModel,
+) -> Result<Vec<ExamResult>> {
+    if let Some(unknown) = answers.iter().find(|answer| {
+        !exercises
+            .iter()
+            .any(|exercise| exercise.id == answer.exercise_id)
+    }) {
+        return Err(TrainerError::UnknownExercise(unknown.exercise_id.clone()).into());
+    }
+    let mut seen = HashSet::new();
+    if let Some(duplicate) = answers
+        .iter()
+        .find(|answer| !seen.insert(answer.exercise_id.as_str()))
+    {
+        return Err(TrainerError::DuplicateAnswer(duplicate.exercise_id.clone()).into());
+    }
+
+    let mut results = Vec::with_capacity(exercises.len());
+    for exercise in exercises {
+        let Some(answer) = answers
+            .iter()
+            .find(|answer| answer.exercise_id == exercise.id)
+        else {
+            results.push(ExamResult {
+                exercise_id: exercise.id.clone(),
+                answer: None,
+                correct: false,
+                score: 0.0,
+            });
+            continue;
+        };
+        let parsed = ParsedAnswer::parse(&answer.answer)?;
+        let grade = exercise.grade(&parsed, Some(policy))?;
+        results.push(ExamResult {
+            exercise_id: exercise.id.clone(),
+            answer: Some(answer.answer.clone()),
+            correct: grade.is_correct(),
+            score: scoring.score(&grade, &parsed).value,
+        });
+    }
+    Ok(results)
+}
+
+/// How many exercises the results have an answer to
+pub fn answered(results: &[ExamResult]) -> usize {
+    results
+        .iter()
+        .filter(|result| result.answer.is_some())
+        .count()
+}
+
+/// Sum of the partial credit of the results
+pub fn total_score(results: &[ExamResult]) -> f64 {
+    results
+        .iter()
+        .fold(0.0, |score, result| score + result.score)
+}
+
+/// Exam with its exercises and the submissions of the students
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Exam {
+    /// Random identifier of the exam
+    pub id: String,
+    /// Identifier of the teacher who created the exam, None if created with the teacher key
+    #[serde(default)]
+    pub owner_id: Option<String>,
+    /// Settings of the exam
+    pub spec: ExamSpec,
+    /// When the exam was created, in seconds since the Unix epoch
+    pub opens_at: u64,
+    /// When the exam closes, in seconds since the Unix epoch
+    pub closes_at: u64,
+    /// Exercises of the exam in their original order, the correct answers are not revealed
+    pub exercises: Vec<EquationExercise>,
+    /// Submissions in the order they were received
+    #[serde(default)]
+    pub submissions: Vec<Submission>,
+}
+
+impl Exam {
+    /// Generates the exercises of the exam, which opens right away
+    pub fn new(mut spec: ExamSpec, now: u64) -> Result<Exam> {
+        if spec.time_limit == 0 {
+            return Err(TrainerError::InvalidTimeLimit(spec.time_limit))
+                .with_context(|| "An exam must be open for at least a second");
+        }
+        let mut rng = rand::thread_rng();
+        // fixing the seed keeps the exercises of the exam reproducible
+        spec.set.exercise.seed = Some(spec.set.exercise.seed.unwrap_or_else(|| rng.gen()));
+        let exercises = spec.set.build()?;
+        Ok(Exam {
+            id: format!("{:032x}", rng.gen::<u128>()),
+            owner_id: None,
+            opens_at: now,
+            closes_at: now.saturating_add(spec.time_limit),
+            spec,
+            exercises,
+            submissions: Vec::new(),
+        })
+    }
+
+    /// Whether answers are still accepted
+    pub fn is_open(&self, now: u64) -> bool {
+        now < self.closes_at
+    }
+
+    /// Exercises as the student sees them, in the student's own order if the exam is shuffled
+    pub fn paper(&self, student: &str, now: u64) -> Result<Vec<EquationExercise>, TrainerError> {
+        if !self.is_open(now) {
+            return Err(TrainerError::ExamClosed(self.id.clone()));
+        }
+        let mut exercises = self.exercises.clone();
+        if self.spec.shuffle {
+            // the same student always gets the same order, e.g. after reloading the page
+            let digest = Sha256::new()
+                .chain_update(self.id.as_bytes())
+                .chain_update(student.as_bytes())
+                .finalize();
+            let mut rng = ChaCha8Rng::from_seed(digest.into());
+            exercises.shuffle(&mut rng);
+        }
+        Ok(exercises)
+    }
+
+    /// Grades and locks the answers of the student, unanswered exercises earn nothing
+    pub fn submit(
+        &mut self,
+        student: &str,
+        answers: &[ExamAnswer],
+        policy: GradingPolicy,
+        scoring: &ScoringModel,
+        now: u64,
+    ) -> Result<&Submission> {
+        if !self.is_open(now) {
+            return Err(TrainerError::ExamClosed(self.id.clone()).into());
+        }
+        if self.submission(student).is_some() {
+            return Err(TrainerError::AlreadySubmitted(student.to_string()).into());
+        }
+        if self.submissions.len() >= MAX_SUBMISSIONS {
+            return Err(TrainerError::ExamFull(self.id.clone()).into());
+        }
+        let results = grade_answers(&self.exercises, answers, policy, scoring)?;
+        self.submissions.push(Submission {
+            student: student.to_string(),
+            submitted_at: now,
+            score: total_score(&results),
+            results,
+        });
+        Ok(self.submissions.last().unwrap())
+    }
+
+    /// Submission of the student regardless of whether the exam has closed
+    pub fn submission(&self, student: &str) -> Option<&Submission> {
+        self.submissions
+            .iter()
+            .find(|submission| submission.student == student)
+    }
+
+    /// Results of the student, released only when the exam has closed
+    pub fn results(&self, student: &str, now: u64) -> Result<Option<&Submission>, TrainerError> {
+        if self.is_open(now) {
+            return Err(TrainerError::ExamNotClosed(self.id.clone()));
+        }
+        Ok(self.submission(student))
+    }
+
+    /// Exercises with their correct answers, released only when the exam has closed
+    pub fn answer_key(&self, now: u64) -> Result<Vec<EquationExercise>> {
+        if self.is_open(now) {
+            return Err(TrainerError::ExamNotClosed(self.id.clone()).into());
+        }
+        let mut exercises = self.exercises.clone();
+        for exercise in &mut exercises {
+            exercise.reveal()?;
+        }
+        Ok(exercises)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_exam(shuffle: bool) -> Exam {
+        let spec = ExamSpec {
+            set: ExerciseSetSpec {
+                count: 10,
+                unique: true,
+                ..ExerciseSetSpec::default()
+            },
+            time_limit: 600,
+            shuffle,
+        };
+        Exam::new(spec, 1000).unwrap()
+    }
+
+    fn right_answer(exercise: &EquationExercise) -> ExamAnswer {
+        let solution = exercise.solve().unwrap();
+        ExamAnswer {
+            exercise_id: exercise.id.clone(),
+            answer: solution.answer.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_papers() {
+        let exam = new_exam(false);
+        assert_eq!(exam.closes_at, 1600);
+        assert!(exam.spec.set.exercise.seed.is_some());
+        assert_eq!(exam.paper("ada", 1000).unwrap(), exam.exercises);
+        assert!(exam
+            .paper("ada", 1000)
+            .unwrap()
+            .iter()
+            .all(|exercise| exercise.correct_answer.is_none()));
+        assert!(matches!(
+            exam.paper("ada", 1600),
+            Err(TrainerError::ExamClosed(_))
+        ));
+
+        let exam = new_exam(true);
+        let ada = exam.paper("ada", 1000).unwrap();
+        assert_eq!(ada, exam.paper("ada", 1001).unwrap());
+        let others: Vec<_> = ["bob", "cy", "dee"]
+            .iter()
+            .map(|student| exam.paper(student, 1000).unwrap())
+            .collect();
+        assert!(others.iter().any(|paper| *paper != ada));
+        let mut ids: Vec<_> = ada.iter().map(|exercise| &exercise.id).collect();
+        let mut original: Vec<_> = exam.exercises.iter().map(|exercise| &exercise.id).collect();
+        ids.sort();
+        original.sort();
+        assert_eq!(ids, original);
+    }
+
+    #[test]
+    fn test_submit_and_results() {
+        let mut exam = new_exam(false);
+        let scoring = ScoringModel::default();
+        let policy = GradingPolicy::default();
+        let answers: Vec<_> = exam.exercises[..7].iter().map(right_answer).collect();
+
+        assert!(matches!(
+            exam.results("ada", 1100),
+            Err(TrainerError::ExamNotClosed(_))
+        ));
+        assert!(exam.answer_key(1100).is_err());
+
+        let submission = exam
+            .submit("ada", &answers, policy, &scoring, 1100)
+            .unwrap();
+        assert_eq!(submission.results.len(), 10);
+        assert_eq!(submission.score, 7.0);
+        let error = exam
+            .submit("ada", &answers, policy, &scoring, 1200)
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<TrainerError>(),
+            Some(TrainerError::AlreadySubmitted(_))
+        ));
+        let error = exam
+            .submit("bob", &answers, policy, &scoring, 1600)
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<TrainerError>(),
+            Some(TrainerError::ExamClosed(_))
+        ));
+
+        assert_eq!(answered(&exam.submission("ada").unwrap().results), 7);
+        let results = exam.results("ada", 1600).unwrap().unwrap();
+        assert!(results.results[0].correct);
+        assert_eq!(results.results[9].answer, None);
+        assert_eq!(exam.results("bob", 1600).unwrap(), None);
+        let key = exam.answer_key(1600).unwrap();
+        assert!(key.iter().all(|exercise| exercise.correct_answer.is_some()));
+    }
+
+    #[test]
+    fn test_full_exam() {
+        let mut exam = new_exam(false);
+        let scoring = ScoringModel::default();
+        let policy = GradingPolicy::default();
+        for student in 0..MAX_SUBMISSIONS {
+            exam.submit(&student.to_string(), &[], policy, &scoring, 1100)
+                .unwrap();
+        }
+        let error = exam.submit("ada", &[], policy, &scoring, 1100).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<TrainerError>(),
+            Some(TrainerError::ExamFull(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_submissions() {
+        let mut exam = new_exam(false);
+        let scoring = ScoringModel::default();
+        let policy = GradingPolicy::default();
+        let unknown = ExamAnswer {
+            exercise_id: "0000000000000000".to_string(),
+            answer: "1".to_string(),
+        };
+        let error = exam
+            .submit("ada", &[unknown], policy, &scoring, 1100)
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<TrainerError>(),
+            Some(TrainerError::UnknownExercise(_))
+        ));
+
+        let twice = [
+            right_answer(&exam.exercises[0]),
+            right_answer(&exam.exercises[0]),
+        ];
+        let error = exam
+            .submit("ada", &twice, policy, &scoring, 1100)
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<TrainerError>(),
+            Some(TrainerError::DuplicateAnswer(_))
+        ));
+
+        let typo = ExamAnswer {
+            answer: "twelve".to_string(),
+            ..right_answer(&exam.exercises[0])
+        };
+        assert!(exam.submit("ada", &[typo], policy, &scoring, 1100).is_err());
+        // rejected submissions do not lock the answers
+        assert!(exam.submission("ada").is_none());
+
+        let spec = ExamSpec {
+            time_limit: 0,
+            ..exam.spec.clone()
+        };
+        assert!(Exam::new(spec, 0).is_err());
+    }
+}
@@ -0,0 +1,387 @@
+#![warn(missing_docs)]
+
+//! PUImURI trainer for Ohms law, power equation and their combinations
+//!
+//! Grading of user answers with verdicts that tell apart wrong values from right values in a wrong prefix or unit
+
+use eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::answer::ParsedAnswer;
+use crate::equations::{EquationExercise, EquationExerciseSolution, EquationUnit};
+use crate::mistakes::Diagnosis;
+use crate::units::SiPrefix;
+use crate::TrainerError;
+
+/// How close an answer has to be to the correct answer to be accepted
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum GradingPolicy {
+    /// Answer must be within the absolute tolerance, in the prefix requested by the exercise
+    Absolute(f64),
+    /// Answer must be within the tolerance relative to the correct answer, e.g. 0.01 for 1 %
+    Relative(f64),
+    /// Answer must match the correct answer when both are rounded to this many significant figures
+    SignificantFigures(u32),
+}
+
+impl Default for GradingPolicy {
+    fn default() -> Self {
+        GradingPolicy::Relative(0.01)
+    }
+}
+
+impl GradingPolicy {
+    /// Returns true if the answer is accepted as the expected answer, both in the same prefix
+    pub fn accepts(&self, answer: f64, expected: f64) -> bool {
+        match *self {
+            GradingPolicy::Absolute(tolerance) => (answer - expected).abs() < tolerance,
+            GradingPolicy::Relative(tolerance) => {
+                if expected == 0.0 {
+                    return answer.abs() < tolerance;
+                }
+                ((answer - expected) / expected).abs() <= tolerance
+            }
+            GradingPolicy::SignificantFigures(figures) => {
+                let answer = round_significant(answer, figures);
+                let expected = round_significant(expected, figures);
+                (answer - expected).abs() <= expected.abs() * 1e-9
+            }
+        }
+    }
+}
+
+impl FromStr for GradingPolicy {
+    type Err = eyre::Report;
+
+    /// Reads a policy written as "absolute:0.01", "relative:0.01", "relative:1%" or "sigfigs:3"
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || TrainerError::InvalidGradingPolicy(s.to_string());
+        let (kind, value) = s.trim().split_once(':').ok_or_else(invalid)?;
+        let value = value.trim();
+        let policy = match kind.trim().to_lowercase().as_str() {
+            "absolute" => GradingPolicy::Absolute(value.parse().map_err(|_| invalid())?),
+            "relative" => match value.strip_suffix('%') {
+                Some(percent) => GradingPolicy::Relative(
+                    percent.trim().parse::<f64>().map_err(|_| invalid())? / 100.0,
+                ),
+                None => GradingPolicy::Relative(value.parse().map_err(|_| invalid())?),
+            },
+            "sigfigs" | "significant" => {
+                GradingPolicy::SignificantFigures(value.parse().map_err(|_| invalid())?)
+            }
+            _ => {
+                return Err(invalid())
+                    .with_context(|| format!("Unknown grading policy \"{}\"", kind))
+            }
+        };
+        match policy {
+            GradingPolicy::Absolute(tolerance) | GradingPolicy::Relative(tolerance)
+                if !(tolerance.is_finite() && tolerance > 0.0) =>
+            {
+                Err(invalid()).with_context(|| "Tolerance must be a positive number".to_string())
+            }
+            GradingPolicy::SignificantFigures(0) => Err(invalid())
+                .with_context(|| "At least one significant figure is needed".to_string()),
+            _ => Ok(policy),
+        }
+    }
+}
+
+/// Rounds the value to the given amount of significant figures
+pub fn round_significant(value: f64, figures: u32) -> f64 {
+    if value == 0.0 || !value.is_finite() {
+        return value;
+    }
+    let decimals = figures as i32 - 1 - value.abs().log10().floor() as i32;
+    if decimals >= 0 {
+        let multiplier = 10f64.powi(decimals);
+        (value * multiplier).round() / multiplier
+    } else {
+        let divider = 10f64.powi(-decimals);
+        (value / divider).round() * divider
+    }
+}
+
+/// Outcome of checking a single answer
+#[derive(Clone, Debug, Serialize, PartialEq)]
+#[serde(tag = "kind")]
+pub enum Verdict {
+    /// Answer is correct
+    Correct,
+    /// The digits are right but the value is off by a power of 1000, e.g. milliamperes given when amperes were asked
+    WrongPrefix {
+        /// Prefix the value given by the user is actually in
+        given: SiPrefix,
+        /// Prefix the user claimed or was asked to answer in
+        expected: SiPrefix,
+        /// Unit of the answer
+        unit: EquationUnit,
+    },
+    /// The value is right but it is written with the wrong unit
+    WrongUnit {
+        /// Unit written by the user
+        given: EquationUnit,
+        /// Unit of the answer
+        expected: EquationUnit,
+    },
+    /// Answer is wrong
+    Incorrect {
+        /// The typical mistake that explains the answer if one was found
+        #[serde(skip_serializing_if = "Option::is_none")]
+        diagnosis: Option<Diagnosis>,
+    },
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::Correct => write!(f, "correct"),
+            Verdict::WrongPrefix {
+                given,
+                expected,
+                unit,
+            } => write!(
+                f,
+                "correct value, but in {}{} not {}{}",
+                given.symbol(),
+                unit.symbol(),
+                expected.symbol(),
+                unit.symbol()
+            ),
+            Verdict::WrongUnit { given, expected } => write!(
+                f,
+                "correct value, but in {} not {}",
+                given.symbol(),
+                expected.symbol()
+            ),
+            Verdict::Incorrect { diagnosis: None } => write!(f, "incorrect"),
+            Verdict::Incorrect {
+                diagnosis: Some(diagnosis),
+            } => write!(f, "incorrect, you probably {}", diagnosis),
+        }
+    }
+}
+
+/// Verdict of an answer together with the solution of the exercise
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Grade {
+    /// How the answer was judged
+    pub verdict: Verdict,
+    /// Solution of the exercise
+    #[serde(flatten)]
+    pub solution: EquationExerciseSolution,
+}
+
+impl Grade {
+    /// Returns true if the answer was fully correct
+    pub fn is_correct(&self) -> bool {
+        self.verdict == Verdict::Correct
+    }
+}
+
+impl EquationExercise {
+    /// Grades the answer typed by the user, using the default policy when none is given
+    pub fn grade(&self, answer: &ParsedAnswer, policy: Option<GradingPolicy>) -> Result<Grade> {
+        let solution = self.solve()?;
+        let policy = policy.unwrap_or_default();
+        let expected = solution.prefixed_answer();
+        let matches = |value: f64| policy.accepts(value, expected);
+
+        let value = answer.in_prefix(solution.prefix);
+        let verdict = match answer.unit {
+            Some(unit) if unit != solution.unit => {
+                if matches(value) {
+                    Verdict::WrongUnit {
+                        given: unit,
+                        expected: solution.unit,
+                    }
+                } else {
+                    Verdict::Incorrect { diagnosis: None }
+                }
+            }
+            _ if matches(value) => Verdict::Correct,
+            _ => {
+                // try reading the digits in every other prefix to find a value that is off by a power of 1000
+                let written = answer.prefix.unwrap_or(match answer.unit {
+                    Some(_) => SiPrefix::Base,
+                    None => solution.prefix,
+                });
+                let wrong_prefix = SiPrefix::ALL
+                    .into_iter()
+                    .filter(|prefix| *prefix != written)
+                    .find(|prefix| {
+                        matches(solution.prefix.from_base(prefix.to_base(answer.value)))
+                    });
+                match wrong_prefix {
+                    Some(given) => Verdict::WrongPrefix {
+                        given,
+                        expected: written,
+                        unit: solution.unit,
+                    },
+                    None => Verdict::Incorrect {
+                        diagnosis: self.diagnose(answer.to_base(solution.prefix), policy)?,
+                    },
+                }
+            }
+        };
+
+        Ok(Grade { verdict, solution })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equations::{EquationExerciseType, EquationVariable};
+    use crate::mistakes::Mistake;
+
+    fn current_exercise(prefix: SiPrefix) -> EquationExercise {
+        EquationExercise {
+            id: String::new(),
+            token: None,
+            exercise_type: EquationExerciseType::OhmsLaw,
+            missing_variable: EquationVariable::Current,
+            given_variables: vec![
+                (EquationVariable::Voltage, 12.0),
+                (EquationVariable::Resistance, 6.0),
+            ],
+            prefixes: vec![(EquationVariable::Current, prefix)],
+            correct_answer: None,
+        }
+    }
+
+    fn grade(exercise: &EquationExercise, answer: &str) -> Verdict {
+        exercise
+            .grade(&ParsedAnswer::parse(answer).unwrap(), None)
+            .unwrap()
+            .verdict
+    }
+
+    #[test]
+    fn test_grade_correct() {
+        let exercise = current_exercise(SiPrefix::Base);
+        assert_eq!(grade(&exercise, "2"), Verdict::Correct);
+        assert_eq!(grade(&exercise, "2 A"), Verdict::Correct);
+        assert_eq!(grade(&exercise, "2000 mA"), Verdict::Correct);
+        assert_eq!(
+            grade(&exercise, "3"),
+            Verdict::Incorrect { diagnosis: None }
+        );
+        assert!(exercise
+            .grade(&ParsedAnswer::parse("2").unwrap(), None)
+            .unwrap()
+            .is_correct());
+    }
+
+    #[test]
+    fn test_grade_wrong_prefix() {
+        let exercise = current_exercise(SiPrefix::Base);
+        let verdict = grade(&exercise, "2000");
+        assert_eq!(
+            verdict,
+            Verdict::WrongPrefix {
+                given: SiPrefix::Milli,
+                expected: SiPrefix::Base,
+                unit: EquationUnit::Ampere
+            }
+        );
+        assert_eq!(verdict.to_string(), "correct value, but in mA not A");
+
+        let exercise = current_exercise(SiPrefix::Milli);
+        assert_eq!(grade(&exercise, "2000"), Verdict::Correct);
+        assert_eq!(
+            grade(&exercise, "2").to_string(),
+            "correct value, but in A not mA"
+        );
+        assert_eq!(
+            grade(&exercise, "2 mA").to_string(),
+            "correct value, but in A not mA"
+        );
+    }
+
+    #[test]
+    fn test_grade_wrong_unit() {
+        let exercise = current_exercise(SiPrefix::Base);
+        let verdict = grade(&exercise, "2 V");
+        assert_eq!(
+            verdict,
+            Verdict::WrongUnit {
+                given: EquationUnit::Volt,
+                expected: EquationUnit::Ampere
+            }
+        );
+        assert_eq!(verdict.to_string(), "correct value, but in V not A");
+        assert_eq!(
+            grade(&exercise, "3 V"),
+            Verdict::Incorrect { diagnosis: None }
+        );
+    }
+
+    #[test]
+    fn test_grade_diagnoses_mistake() {
+        let exercise = current_exercise(SiPrefix::Base);
+        let verdict = grade(&exercise, "72");
+        assert!(matches!(
+            verdict,
+            Verdict::Incorrect {
+                diagnosis: Some(Diagnosis {
+                    mistake: Mistake::MultipliedInsteadOfDivided,
+                    ..
+                })
+            }
+        ));
+        assert_eq!(
+            verdict.to_string(),
+            "incorrect, you probably multiplied instead of dividing (I = U * R)"
+        );
+    }
+
+    #[test]
+    fn test_grading_policies() {
+        assert!(GradingPolicy::Absolute(0.01).accepts(6.005, 6.0));
+        assert!(!GradingPolicy::Absolute(0.01).accepts(6.02, 6.0));
+
+        assert!(GradingPolicy::Relative(0.01).accepts(0.0042, 0.004166));
+        assert!(!GradingPolicy::Relative(0.01).accepts(0.005, 0.004166));
+        assert!(GradingPolicy::Relative(0.01).accepts(1210.0, 1200.0));
+        assert!(!GradingPolicy::Relative(0.01).accepts(1220.0, 1200.0));
+
+        assert!(GradingPolicy::SignificantFigures(2).accepts(0.0042, 0.004166));
+        assert!(!GradingPolicy::SignificantFigures(3).accepts(0.0042, 0.004166));
+        assert!(GradingPolicy::SignificantFigures(3).accepts(0.00417, 0.004166));
+        assert!(GradingPolicy::SignificantFigures(2).accepts(1200.0, 1234.0));
+    }
+
+    #[test]
+    fn test_parse_grading_policy() {
+        assert_eq!(
+            "absolute:0.01".parse::<GradingPolicy>().unwrap(),
+            GradingPolicy::Absolute(0.01)
+        );
+        assert_eq!(
+            "relative:2%".parse::<GradingPolicy>().unwrap(),
+            GradingPolicy::Relative(0.02)
+        );
+        assert_eq!(
+            "relative:0.05".parse::<GradingPolicy>().unwrap(),
+            GradingPolicy::Relative(0.05)
+        );
+        assert_eq!(
+            "sigfigs:3".parse::<GradingPolicy>().unwrap(),
+            GradingPolicy::SignificantFigures(3)
+        );
+        assert!("sigfigs:0".parse::<GradingPolicy>().is_err());
+        assert!("relative:-1".parse::<GradingPolicy>().is_err());
+        assert!("exact".parse::<GradingPolicy>().is_err());
+        assert!("fuzzy:1".parse::<GradingPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_round_significant() {
+        assert_eq!(round_significant(0.004166, 2), 0.0042);
+        assert_eq!(round_significant(1234.0, 2), 1200.0);
+        assert_eq!(round_significant(-5.55, 1), -6.0);
+    }
+}
@@ -4,10 +4,26 @@
 //!
 //! This library provides a trainer implementation that creates and solves excercesis
 
-use equations::{EquationExerciseSolution, EquationVariable};
+use equations::{EquationExerciseSolution, EquationExerciseType, EquationVariable};
 use thiserror::Error;
 
+pub mod accounts;
+pub mod adaptive;
+pub mod answer;
+pub mod classroom;
+pub mod difficulty;
 pub mod equations;
+pub mod exam;
+pub mod grading;
+pub mod mistakes;
+pub mod progress;
+pub mod scheduler;
+pub mod scoring;
+pub mod session;
+pub mod signing;
+pub mod spec;
+pub mod storage;
+pub mod units;
 
 /// Error types that this trainer library can return
 #[derive(Error, Debug)]
@@ -15,10 +31,94 @@ pub enum TrainerError {
     /// Programmer or end-user if allowed by programmer is trying to initialize excersise builder with values that do not make sense
     #[error("Minimum value is larger than maximum value")]
     MinLargerThanMax,
+    /// Range settings of the excersise builder can not produce usable non-zero values for the variable
+    #[error("Range of {0:?} can not produce a usable value")]
+    UnsatisfiableRange(EquationVariable),
     /// Variable is missing somewhere
     #[error("Variable is missing in definitions")]
     MissingVariable(EquationVariable),
+    /// Exercise asks for a variable that its type of equation does not solve, e.g. power in Ohms law
+    #[error("{1:?} can not be asked in a {0:?} exercise")]
+    InvalidMissingVariable(EquationExerciseType, EquationVariable),
     /// The exercise resolver is getting a different result than what is indicated by the ecercise itself
     #[error("Exercise solution does not match with solved solution")]
     EquationResolveError(EquationExerciseSolution),
+    /// User typed an answer that can not be read as a number with an optional prefix and unit
+    #[error("Answer \"{0}\" is not a valid number")]
+    InvalidAnswer(String),
+    /// Grading policy given in configuration could not be read
+    #[error("Grading policy \"{0}\" is not valid")]
+    InvalidGradingPolicy(String),
+    /// Difficulty level is not a number between 1 and 10
+    #[error("Difficulty \"{0}\" is not a level between 1 and 10")]
+    InvalidDifficulty(String),
+    /// None of the exercise types the builder may pick from can be generated with its settings
+    #[error("No exercise type can be generated with the settings")]
+    NoExerciseType,
+    /// Set of exercises is empty, too large or can not be filled with different exercises
+    #[error("Set of {0} exercises can not be generated")]
+    InvalidSetSize(usize),
+    /// Training session does not exist or has expired
+    #[error("Session \"{0}\" does not exist")]
+    UnknownSession(String),
+    /// Exercise was not issued in the session or it has already been answered
+    #[error("Exercise \"{0}\" was not issued in this session or it has already been answered")]
+    ExerciseNotIssued(String),
+    /// Exercise sent back by the client is unsigned or was modified after it was signed
+    #[error("Exercise token is missing or does not match the exercise")]
+    InvalidToken,
+    /// Exam is open for no time at all
+    #[error("Time limit of {0} seconds is not valid")]
+    InvalidTimeLimit(u64),
+    /// Exam does not exist
+    #[error("Exam \"{0}\" does not exist")]
+    UnknownExam(String),
+    /// Exam no longer accepts answers
+    #[error("Exam \"{0}\" has closed")]
+    ExamClosed(String),
+    /// Results and answers of the exam are not released before it closes
+    #[error("Exam \"{0}\" has not closed yet")]
+    ExamNotClosed(String),
+    /// Student has already submitted their answers to the exam
+    #[error("Answers of \"{0}\" have already been submitted")]
+    AlreadySubmitted(String),
+    /// Exam has taken as many submissions as it can keep
+    #[error("Exam \"{0}\" takes no more submissions")]
+    ExamFull(String),
+    /// Answered exercise is not part of the exam or assignment
+    #[error("Exercise \"{0}\" is not part of the exam or assignment")]
+    UnknownExercise(String),
+    /// Exercise is answered more than once in the same submission
+    #[error("Exercise \"{0}\" is answered more than once")]
+    DuplicateAnswer(String),
+    /// Class does not exist
+    #[error("Class \"{0}\" does not exist")]
+    UnknownClass(String),
+    /// No class has the join code
+    #[error("Join code \"{0}\" is not valid")]
+    InvalidJoinCode(String),
+    /// Student has not joined the class
+    #[error("\"{0}\" is not enrolled in the class")]
+    NotEnrolled(String),
+    /// Class has as many students as it can take
+    #[error("Class \"{0}\" takes no more students")]
+    ClassFull(String),
+    /// Assignment does not exist in the class
+    #[error("Assignment \"{0}\" does not exist")]
+    UnknownAssignment(String),
+    /// Answers submitted on time can not be replaced by late ones
+    #[error("Answers to assignment \"{0}\" were submitted on time and can no longer be replaced")]
+    SubmittedOnTime(String),
+    /// Username or password does not meet the requirements
+    #[error("{0}")]
+    InvalidAccount(String),
+    /// Another account has the username
+    #[error("Username \"{0}\" is taken")]
+    UsernameTaken(String),
+    /// User does not exist
+    #[error("User \"{0}\" does not exist")]
+    UnknownUser(String),
+    /// Username or password is wrong, which one is not told
+    #[error("Wrong username or password")]
+    LoginFailed,
 }
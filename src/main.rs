@@ -4,18 +4,56 @@
 //!
 //! This web server serves REST interface for training the "PUImURI" related equations and the frontend code
 
+use api::account::{self, AccountStore};
+use api::classroom::{self, ClassStore};
+use api::exam::{self, ExamStore};
+use api::oidc::{self, OidcConfig, OidcProvider};
+use api::session::{self, ProgressStore, SessionStore};
+use api::{blocking, ApiError, Json, Path, Query};
 use axum::{
-    extract::Path,
-    http::StatusCode,
-    routing::{get, post},
-    Json, Router,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    middleware,
+    routing::{get, post, put},
+    Router,
 };
 use eyre::Result;
-use puimuri_trainer::equations::{EquationExercise, EquationExerciseSolution};
+use puimuri_trainer::accounts::Role;
+use puimuri_trainer::answer::ParsedAnswer;
+use puimuri_trainer::equations::EquationExercise;
+use puimuri_trainer::grading::{Grade, GradingPolicy};
+use puimuri_trainer::scoring::{Score, ScoringModel};
+use puimuri_trainer::signing::{ExerciseSigner, KEY_LENGTH};
+use puimuri_trainer::spec::{ExerciseSetSpec, ExerciseSpec, MAX_SET_SIZE};
+use puimuri_trainer::storage::Storage;
+use puimuri_trainer::TrainerError;
+use serde::{Deserialize, Serialize};
 use std::env;
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
 use tower_http::services::ServeDir;
 use tower_http::trace::TraceLayer;
 
+mod api;
+
+/// Settings shared by all request handlers
+#[derive(Clone)]
+struct AppState {
+    grading_policy: GradingPolicy,
+    scoring_model: ScoringModel,
+    signer: ExerciseSigner,
+    reveal_answers: bool,
+    teacher_key: Option<String>,
+    secure_cookies: bool,
+    accounts: AccountStore,
+    oidc: Option<OidcProvider>,
+    sessions: SessionStore,
+    progress: ProgressStore,
+    exams: ExamStore,
+    classes: ClassStore,
+    storage: Option<Arc<Storage>>,
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     #[cfg(debug_assertions)]
@@ -26,12 +64,123 @@ async fn main() -> Result<()> {
     let frontend_dir = env::var("PUIMURI_FRONTEND_DIR").unwrap_or("static".to_string());
     let port = env::var("PORT").unwrap_or("8000".to_string());
     let address = env::var("ADDRESS").unwrap_or("127.0.0.1".to_string());
+    let grading_policy = match env::var("PUIMURI_GRADING_POLICY") {
+        Ok(policy) => policy.parse()?,
+        Err(_) => GradingPolicy::default(),
+    };
+    let signer = match env::var("PUIMURI_SECRET_KEY") {
+        Ok(key) if key.is_empty() => eyre::bail!("PUIMURI_SECRET_KEY is empty"),
+        Ok(key) => {
+            if key.len() < KEY_LENGTH {
+                tracing::warn!(
+                    "PUIMURI_SECRET_KEY is shorter than {} bytes, use a longer random key",
+                    KEY_LENGTH
+                );
+            }
+            ExerciseSigner::new(key.as_bytes())
+        }
+        Err(_) => {
+            tracing::warn!("PUIMURI_SECRET_KEY is not set, exercises handed out before a restart can not be answered after it");
+            ExerciseSigner::random()
+        }
+    };
+
+    let reveal_answers = env::var("PUIMURI_REVEAL_ANSWERS").is_ok_and(|value| value == "true");
+    let teacher_key = env::var("PUIMURI_TEACHER_KEY")
+        .ok()
+        .filter(|key| !key.is_empty());
+    let storage = match env::var("PUIMURI_DATABASE") {
+        Ok(path) => Some(Arc::new(Storage::open(path)?)),
+        Err(_) => None,
+    };
+    // cookies marked secure are only sent over https
+    let secure_cookies = env::var("PUIMURI_SECURE_COOKIES").is_ok_and(|value| value == "true");
+    let accounts = AccountStore::load(storage.clone())?;
+    if let (Ok(username), Ok(password)) = (
+        env::var("PUIMURI_ADMIN_USER"),
+        env::var("PUIMURI_ADMIN_PASSWORD"),
+    ) {
+        accounts
+            .ensure(&username, &password, Role::Admin)
+            .await
+            .map_err(|error| eyre::eyre!("Unable to create the admin: {:?}", error))?;
+    }
+    let oidc = match OidcConfig::from_env()? {
+        Some(config) => Some(OidcProvider::discover(config).await?),
+        None => None,
+    };
+
+    let state = AppState {
+        grading_policy,
+        scoring_model: ScoringModel::default(),
+        signer,
+        reveal_answers,
+        teacher_key,
+        secure_cookies,
+        accounts,
+        oidc,
+        sessions: SessionStore::new(storage.clone(), session::MAX_SESSIONS),
+        progress: ProgressStore::new(storage.clone(), session::MAX_SESSIONS),
+        exams: ExamStore::new(storage.clone(), exam::MAX_EXAMS),
+        classes: ClassStore::new(storage.clone()),
+        storage,
+    };
+
+    let teachers = Router::new()
+        .route("/api/exam", post(exam::create))
+        .route("/api/exam/{id}", get(exam::show))
+        .route("/api/class", post(classroom::create))
+        .route("/api/class/{id}", get(classroom::show))
+        .route("/api/class/{id}/results", get(classroom::results))
+        .route("/api/class/{id}/assignment", post(classroom::assign))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            account::teachers_only,
+        ));
+    let admins = Router::new()
+        .route(
+            "/api/admin/users",
+            get(account::users).post(account::create_user),
+        )
+        .route("/api/admin/users/{username}", put(account::set_role))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            account::admins_only,
+        ));
 
     let app = Router::new()
         .fallback_service(ServeDir::new(frontend_dir))
         .route("/api/equation", get(equation))
+        .route("/api/equation/batch", post(equation_batch))
+        .route("/api/equation/answer", post(equation_answer_text))
+        .route("/api/equation/answer/batch", post(equation_answer_batch))
         .route("/api/equation/answer/{answer}", post(equation_answer))
-        .layer(TraceLayer::new_for_http());
+        .route("/api/session", post(session::create))
+        .route("/api/session/{id}", get(session::show))
+        .route("/api/session/{id}/exercise", get(session::exercise))
+        .route("/api/session/{id}/answer", post(session::answer))
+        .route("/api/account/register", post(account::register))
+        .route("/api/account/login", post(account::login))
+        .route("/api/account/logout", post(account::logout))
+        .route("/api/account/me", get(account::me))
+        .route("/api/oidc/login", get(oidc::login))
+        .route("/api/oidc/callback", get(oidc::callback))
+        .route("/api/exam/{id}/paper", get(exam::paper))
+        .route("/api/exam/{id}/submit", post(exam::submit))
+        .route("/api/exam/{id}/results", get(exam::results))
+        .route("/api/class/join", post(classroom::join))
+        .route(
+            "/api/class/{id}/assignment/{assignment}",
+            get(classroom::assignment),
+        )
+        .route(
+            "/api/class/{id}/assignment/{assignment}/submit",
+            post(classroom::submit),
+        )
+        .merge(teachers)
+        .merge(admins)
+        .layer(TraceLayer::new_for_http())
+        .with_state(state);
 
     let listener =
         tokio::net::TcpListener::bind(format!("{address}:{port}", address = address, port = port))
@@ -41,18 +190,244 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-async fn equation() -> (StatusCode, Json<EquationExercise>) {
-    let exercise = EquationExercise::builder().build_with_random_exercisetype();
-    (StatusCode::OK, Json(exercise))
+/// Adaptive mode of a single exercise, the learner is known by their training session
+#[derive(Deserialize)]
+struct AdaptiveQuery {
+    #[serde(default)]
+    adaptive: bool,
+    session: Option<String>,
+}
+
+async fn equation(
+    State(state): State<AppState>,
+    Query(spec): Query<ExerciseSpec>,
+    Query(mode): Query<AdaptiveQuery>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<EquationExercise>), ApiError> {
+    if !mode.adaptive {
+        let mut exercise =
+            blocking(move || Ok(spec.builder()?.build_with_random_exercisetype()?)).await?;
+        state.hand_out(&mut exercise, &headers, None).await?;
+        return Ok((StatusCode::OK, Json(exercise)));
+    }
+    let Some(id) = mode.session else {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "InvalidRequest",
+            "Adaptive exercises need the session of the learner",
+        ));
+    };
+    // the exercise is issued in the session, so its answer moves the difficulty of the session
+    let mut exercise = state
+        .sessions
+        .update(&id, move |training| {
+            Ok(training.next_adaptive_exercise(&spec, session::now())?)
+        })
+        .await?;
+    state.hand_out(&mut exercise, &headers, Some(&id)).await?;
+    Ok((StatusCode::OK, Json(exercise)))
+}
+
+/// Exercises generated at once
+#[derive(Serialize)]
+struct BatchResponse {
+    exercises: Vec<EquationExercise>,
+}
+
+async fn equation_batch(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(spec): Json<ExerciseSetSpec>,
+) -> Result<(StatusCode, Json<BatchResponse>), ApiError> {
+    let mut exercises = blocking(move || Ok(spec.build()?)).await?;
+    for exercise in &mut exercises {
+        state.hand_out(exercise, &headers, None).await?;
+    }
+    Ok((StatusCode::OK, Json(BatchResponse { exercises })))
+}
+
+/// Graded answer together with the partial credit it earned
+#[derive(Serialize)]
+struct AnswerResponse {
+    #[serde(flatten)]
+    grade: Grade,
+    score: Score,
+}
+
+impl AppState {
+    /// Whether the request carries the teacher key
+    fn has_teacher_key(&self, headers: &HeaderMap) -> bool {
+        match (&self.teacher_key, headers.get("X-Teacher-Key")) {
+            (Some(key), Some(given)) => given.as_bytes().ct_eq(key.as_bytes()).into(),
+            _ => false,
+        }
+    }
+
+    /// Correct answers are sent along with exercises when enabled for everyone or when asked by a teacher
+    fn may_reveal(&self, headers: &HeaderMap) -> bool {
+        self.reveal_answers || self.require_role(headers, Role::Teacher).is_ok()
+    }
+
+    /// Signs the exercise, reveals its answer if the client may see it and records it in the database
+    async fn hand_out(
+        &self,
+        exercise: &mut EquationExercise,
+        headers: &HeaderMap,
+        session_id: Option<&str>,
+    ) -> Result<(), ApiError> {
+        self.signer.sign(exercise, session_id);
+        if self.may_reveal(headers) {
+            exercise.reveal()?;
+        }
+        if let Some(storage) = self.storage.clone() {
+            let exercise = exercise.clone();
+            let session_id = session_id.map(str::to_string);
+            blocking(move || {
+                storage.record_exercise(&exercise, session_id.as_deref(), session::now())?;
+                Ok(())
+            })
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Grades the answer, the session must be the one the exercise was issued in, None for free exercises
+    fn grade(
+        &self,
+        exercise: &EquationExercise,
+        answer: &ParsedAnswer,
+        session_id: Option<&str>,
+    ) -> Result<AnswerResponse, ApiError> {
+        // only exercises handed out by this server can be answered, and those of a session only in it
+        self.signer.verify(exercise, session_id)?;
+        let grade = exercise.grade(answer, Some(self.grading_policy))?;
+        let score = self.scoring_model.score(&grade, answer);
+        Ok(AnswerResponse { grade, score })
+    }
+
+    /// Records the graded attempt in the database, the text is the answer as the user gave it
+    async fn record(
+        &self,
+        exercise: &EquationExercise,
+        session_id: Option<&str>,
+        text: &str,
+        response: &AnswerResponse,
+    ) -> Result<(), ApiError> {
+        if let Some(storage) = self.storage.clone() {
+            let (exercise, session_id, text) = (
+                exercise.clone(),
+                session_id.map(str::to_string),
+                text.to_string(),
+            );
+            let (grade, score) = (response.grade.clone(), response.score.clone());
+            blocking(move || {
+                storage.record_attempt(
+                    &exercise,
+                    session_id.as_deref(),
+                    &text,
+                    &grade,
+                    &score,
+                    session::now(),
+                )?;
+                Ok(())
+            })
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn check(
+        &self,
+        exercise: &EquationExercise,
+        text: &str,
+        answer: &ParsedAnswer,
+    ) -> Result<(StatusCode, Json<AnswerResponse>), ApiError> {
+        let response = self.grade(exercise, answer, None)?;
+        self.record(exercise, None, text, &response).await?;
+        if response.grade.is_correct() {
+            return Ok((StatusCode::OK, Json(response))); // answer is correct within the grading policy
+        }
+        Ok((StatusCode::PRECONDITION_FAILED, Json(response))) // answer is way off, in a wrong prefix or unit, or incorrect
+    }
 }
 
 async fn equation_answer(
+    State(state): State<AppState>,
     Path(answer): Path<f64>,
     Json(exercise): Json<EquationExercise>,
-) -> (StatusCode, Json<EquationExerciseSolution>) {
-    let solution = exercise.solve().unwrap();
-    if (answer - solution.answer).abs() < 0.01 {
-        return (StatusCode::OK, Json(solution)); // answer is correct within certain decimal point
+) -> Result<(StatusCode, Json<AnswerResponse>), ApiError> {
+    state
+        .check(&exercise, &answer.to_string(), &ParsedAnswer::from(answer))
+        .await
+}
+
+/// Answer as typed by the user together with the exercise it answers
+#[derive(Deserialize)]
+struct AnswerRequest {
+    exercise: EquationExercise,
+    answer: String,
+}
+
+async fn equation_answer_text(
+    State(state): State<AppState>,
+    Json(request): Json<AnswerRequest>,
+) -> Result<(StatusCode, Json<AnswerResponse>), ApiError> {
+    let answer = ParsedAnswer::parse(&request.answer)?;
+    state
+        .check(&request.exercise, &request.answer, &answer)
+        .await
+}
+
+/// Many answers graded in one request, e.g. when a tablet comes back online
+#[derive(Deserialize)]
+struct BatchAnswerRequest {
+    answers: Vec<AnswerRequest>,
+}
+
+/// Result of one answer of a batch, either graded or the reason it could not be
+#[derive(Serialize)]
+#[serde(untagged)]
+enum BatchAnswerResult {
+    Graded {
+        correct: bool,
+        #[serde(flatten)]
+        response: AnswerResponse,
+    },
+    Failed(ApiError),
+}
+
+/// Results in the order of the answers
+#[derive(Serialize)]
+struct BatchAnswerResponse {
+    results: Vec<BatchAnswerResult>,
+}
+
+async fn equation_answer_batch(
+    State(state): State<AppState>,
+    Json(request): Json<BatchAnswerRequest>,
+) -> Result<(StatusCode, Json<BatchAnswerResponse>), ApiError> {
+    if request.answers.len() > MAX_SET_SIZE {
+        return Err(TrainerError::InvalidSetSize(request.answers.len()).into());
+    }
+    let mut results = Vec::with_capacity(request.answers.len());
+    for request in &request.answers {
+        let graded = match ParsedAnswer::parse(&request.answer)
+            .map_err(ApiError::from)
+            .and_then(|answer| state.grade(&request.exercise, &answer, None))
+        {
+            Ok(response) => state
+                .record(&request.exercise, None, &request.answer, &response)
+                .await
+                .map(|_| response),
+            Err(error) => Err(error),
+        };
+        results.push(match graded {
+            Ok(response) => BatchAnswerResult::Graded {
+                correct: response.grade.is_correct(),
+                response,
+            },
+            Err(error) => BatchAnswerResult::Failed(error),
+        });
     }
-    (StatusCode::PRECONDITION_FAILED, Json(solution)) // answer is way off or incorrect
+    Ok((StatusCode::OK, Json(BatchAnswerResponse { results })))
 }
@@ -0,0 +1,276 @@
+#![warn(missing_docs)]
+
+//! PUImURI trainer for Ohms law, power equation and their combinations
+//!
+//! Diagnosis of wrong answers by recomputing the result with the formulas learners typically get wrong
+
+use eyre::Result;
+use serde::Serialize;
+use std::fmt;
+
+use crate::equations::{EquationExercise, EquationExerciseType, EquationVariable};
+use crate::grading::GradingPolicy;
+
+/// Typical mistake made when applying the formulas
+#[derive(Copy, Clone, Debug, Serialize, PartialEq, Eq, Hash)]
+pub enum Mistake {
+    /// Multiplied where the formula divides, e.g. U * R instead of U / R
+    MultipliedInsteadOfDivided,
+    /// Divided where the formula multiplies, e.g. R / I instead of R * I
+    DividedInsteadOfMultiplied,
+    /// Divided the wrong way around, e.g. R / U instead of U / R
+    InvertedDivision,
+    /// Forgot to square, e.g. U / R instead of U^2 / R
+    MissingSquare,
+    /// Forgot the square root, e.g. P / R instead of √(P / R)
+    MissingSquareRoot,
+}
+
+impl fmt::Display for Mistake {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mistake::MultipliedInsteadOfDivided => write!(f, "multiplied instead of dividing"),
+            Mistake::DividedInsteadOfMultiplied => write!(f, "divided instead of multiplying"),
+            Mistake::InvertedDivision => write!(f, "divided the wrong way around"),
+            Mistake::MissingSquare => write!(f, "forgot to square"),
+            Mistake::MissingSquareRoot => write!(f, "forgot the square root"),
+        }
+    }
+}
+
+/// The mistake the learner probably made and the formula that gives their answer
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct Diagnosis {
+    /// What went wrong
+    pub mistake: Mistake,
+    /// The mistaken formula, e.g. "I = U * R"
+    pub formula: String,
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.mistake, self.formula)
+    }
+}
+
+impl EquationExercise {
+    /// Results of the typical mistaken formulas for this exercise in base units
+    pub fn mistaken_answers(&self) -> Result<Vec<(Mistake, &'static str, f64)>> {
+        use EquationVariable::*;
+        use Mistake::*;
+
+        let get_value = |variable: EquationVariable| self.given_value(variable);
+
+        let candidates = match (self.exercise_type, self.missing_variable) {
+            (EquationExerciseType::OhmsLaw, Voltage) => {
+                let (r, i) = (get_value(Resistance)?, get_value(Current)?);
+                vec![
+                    (DividedInsteadOfMultiplied, "U = R / I", r / i),
+                    (DividedInsteadOfMultiplied, "U = I / R", i / r),
+                ]
+            }
+            (EquationExerciseType::OhmsLaw, Current) => {
+                let (u, r) = (get_value(Voltage)?, get_value(Resistance)?);
+                vec![
+                    (MultipliedInsteadOfDivided, "I = U * R", u * r),
+                    (InvertedDivision, "I = R / U", r / u),
+                ]
+            }
+            (EquationExerciseType::OhmsLaw, Resistance) => {
+                let (u, i) = (get_value(Voltage)?, get_value(Current)?);
+                vec![
+                    (MultipliedInsteadOfDivided, "R = U * I", u * i),
+                    (InvertedDivision, "R = I / U", i / u),
+                ]
+            }
+            (EquationExerciseType::Power, Power) => {
+                let (u, i) = (get_value(Voltage)?, get_value(Current)?);
+                vec![
+                    (DividedInsteadOfMultiplied, "P = U / I", u / i),
+                    (DividedInsteadOfMultiplied, "P = I / U", i / u),
+                ]
+            }
+            (EquationExerciseType::Power, Voltage) => {
+                let (p, i) = (get_value(Power)?, get_value(Current)?);
+                vec![
+                    (MultipliedInsteadOfDivided, "U = P * I", p * i),
+                    (InvertedDivision, "U = I / P", i / p),
+                ]
+            }
+            (EquationExerciseType::Power, Current) => {
+                let (p, u) = (get_value(Power)?, get_value(Voltage)?);
+                vec![
+                    (MultipliedInsteadOfDivided, "I = P * U", p * u),
+                    (InvertedDivision, "I = U / P", u / p),
+                ]
+            }
+            (EquationExerciseType::Combined, Power) => {
+                let (u, r) = (get_value(Voltage)?, get_value(Resistance)?);
+                vec![
+                    (MissingSquare, "P = U / R", u / r),
+                    (MultipliedInsteadOfDivided, "P = U^2 * R", u * u * r),
+                    (InvertedDivision, "P = R / U^2", r / (u * u)),
+                ]
+            }
+            (EquationExerciseType::Combined, Current) => {
+                let (p, r) = (get_value(Power)?, get_value(Resistance)?);
+                vec![
+                    (MissingSquareRoot, "I = P / R", p / r),
+                    (MultipliedInsteadOfDivided, "I = √(P * R)", (p * r).sqrt()),
+                    (InvertedDivision, "I = √(R / P)", (r / p).sqrt()),
+                ]
+            }
+            (EquationExerciseType::Combined, Voltage) => {
+                let (p, r) = (get_value(Power)?, get_value(Resistance)?);
+                vec![
+                    (MissingSquareRoot, "U = P * R", p * r),
+                    (DividedInsteadOfMultiplied, "U = √(P / R)", (p / r).sqrt()),
+                ]
+            }
+            (EquationExerciseType::Combined, Resistance) => {
+                let (p, u) = (get_value(Power)?, get_value(Voltage)?);
+                vec![
+                    (MissingSquare, "R = U / P", u / p),
+                    (MultipliedInsteadOfDivided, "R = U^2 * P", u * u * p),
+                    (InvertedDivision, "R = P / U^2", p / (u * u)),
+                ]
+            }
+            _ => vec![],
+        };
+
+        Ok(candidates)
+    }
+
+    /// Finds the mistake that explains the answer, answer is in base units
+    pub fn diagnose(&self, answer: f64, policy: GradingPolicy) -> Result<Option<Diagnosis>> {
+        let prefix = self.prefix_for(self.missing_variable);
+        let answer = prefix.from_base(answer);
+        let diagnosis = self
+            .mistaken_answers()?
+            .into_iter()
+            .find(|(_, _, value)| policy.accepts(answer, prefix.from_base(*value)))
+            .map(|(mistake, formula, _)| Diagnosis {
+                mistake,
+                formula: formula.to_string(),
+            });
+        Ok(diagnosis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::SiPrefix;
+
+    fn exercise(
+        exercise_type: EquationExerciseType,
+        missing_variable: EquationVariable,
+        given_variables: Vec<(EquationVariable, f64)>,
+    ) -> EquationExercise {
+        EquationExercise {
+            id: String::new(),
+            token: None,
+            exercise_type,
+            missing_variable,
+            given_variables,
+            prefixes: vec![],
+            correct_answer: None,
+        }
+    }
+
+    #[test]
+    fn test_diagnose_multiplied_instead_of_divided() {
+        let exercise = exercise(
+            EquationExerciseType::OhmsLaw,
+            EquationVariable::Current,
+            vec![
+                (EquationVariable::Voltage, 12.0),
+                (EquationVariable::Resistance, 6.0),
+            ],
+        );
+        let diagnosis = exercise
+            .diagnose(72.0, GradingPolicy::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(diagnosis.mistake, Mistake::MultipliedInsteadOfDivided);
+        assert_eq!(diagnosis.formula, "I = U * R");
+        assert_eq!(
+            diagnosis.to_string(),
+            "multiplied instead of dividing (I = U * R)"
+        );
+        assert_eq!(
+            exercise
+                .diagnose(0.5, GradingPolicy::default())
+                .unwrap()
+                .unwrap()
+                .mistake,
+            Mistake::InvertedDivision
+        );
+        assert_eq!(
+            exercise.diagnose(3.0, GradingPolicy::default()).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_diagnose_missing_square() {
+        let exercise = exercise(
+            EquationExerciseType::Combined,
+            EquationVariable::Power,
+            vec![
+                (EquationVariable::Voltage, 12.0),
+                (EquationVariable::Resistance, 6.0),
+            ],
+        );
+        assert_eq!(
+            exercise
+                .diagnose(2.0, GradingPolicy::default())
+                .unwrap()
+                .unwrap()
+                .mistake,
+            Mistake::MissingSquare
+        );
+    }
+
+    #[test]
+    fn test_diagnose_missing_square_root() {
+        let exercise = exercise(
+            EquationExerciseType::Combined,
+            EquationVariable::Current,
+            vec![
+                (EquationVariable::Power, 100.0),
+                (EquationVariable::Resistance, 4.0),
+            ],
+        );
+        assert_eq!(
+            exercise
+                .diagnose(25.0, GradingPolicy::default())
+                .unwrap()
+                .unwrap()
+                .mistake,
+            Mistake::MissingSquareRoot
+        );
+    }
+
+    #[test]
+    fn test_diagnose_with_prefix() {
+        let mut exercise = exercise(
+            EquationExerciseType::OhmsLaw,
+            EquationVariable::Current,
+            vec![
+                (EquationVariable::Voltage, 12.0),
+                (EquationVariable::Resistance, 4000.0),
+            ],
+        );
+        exercise.prefixes = vec![(EquationVariable::Current, SiPrefix::Milli)];
+        // 4000 / 12 = 333.33 A
+        assert_eq!(
+            exercise
+                .diagnose(333.333_333, GradingPolicy::default())
+                .unwrap()
+                .unwrap()
+                .mistake,
+            Mistake::InvertedDivision
+        );
+    }
+}
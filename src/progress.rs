@@ -0,0 +1,83 @@
+#![warn(missing_docs)]
+
+//! PUImURI trainer for Ohms law, power equation and their combinations
+//!
+//! Progress of a user that carries over from one training session to the next
+
+use serde::{Deserialize, Serialize};
+
+use crate::adaptive::AdaptiveDifficulty;
+use crate::difficulty::Difficulty;
+use crate::equations::EquationExercise;
+use crate::scheduler::{ExerciseKind, Scheduler};
+
+/// Leitner boxes and difficulty level of a user, kept with their account instead of a single session
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Progress {
+    /// Identifier of the user
+    pub user_id: String,
+    /// Leitner boxes new sessions of the user start from
+    #[serde(default)]
+    pub scheduler: Scheduler,
+    /// Adaptive difficulty new sessions of the user start on, None before their first answer
+    #[serde(default)]
+    pub adaptive: Option<AdaptiveDifficulty>,
+    /// When the progress last changed, in seconds since the Unix epoch
+    pub updated_at: u64,
+}
+
+impl Progress {
+    /// Progress of a user who has not answered anything yet
+    pub fn new(user_id: &str, now: u64) -> Progress {
+        Progress {
+            user_id: user_id.to_string(),
+            scheduler: Scheduler::default(),
+            adaptive: None,
+            updated_at: now,
+        }
+    }
+
+    /// Records an answer given in any session of the user, the first answer starts the adaptive difficulty on the level
+    pub fn record(
+        &mut self,
+        exercise: &EquationExercise,
+        correct: bool,
+        level: Difficulty,
+        now: u64,
+    ) {
+        self.scheduler.record(ExerciseKind::from(exercise), correct);
+        self.adaptive
+            .get_or_insert_with(|| AdaptiveDifficulty::new(level))
+            .record(correct);
+        self.updated_at = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equations::{EquationExerciseBuilder, EquationExerciseType};
+
+    #[test]
+    fn test_record_answers() {
+        let exercise = EquationExerciseBuilder::new()
+            .set_type(EquationExerciseType::Power)
+            .build()
+            .unwrap();
+        let kind = ExerciseKind::from(&exercise);
+        let level = Difficulty::new(4).unwrap();
+        let mut progress = Progress::new("ada", 0);
+        assert_eq!(progress.adaptive, None);
+        progress.record(&exercise, true, level, 10);
+        progress.record(&exercise, true, Difficulty::default(), 20);
+        assert_eq!(progress.scheduler.level(kind), 2);
+        assert_eq!(progress.updated_at, 20);
+        progress.record(&exercise, false, level, 30);
+        assert_eq!(progress.scheduler.level(kind), 0);
+
+        // the level follows the answers from the level of the first session
+        let adaptive = progress.adaptive.unwrap();
+        assert_eq!(adaptive.level, level);
+        assert_eq!(adaptive.recent, [true, true, false]);
+    }
+}
@@ -0,0 +1,152 @@
+#![warn(missing_docs)]
+
+//! PUImURI trainer for Ohms law, power equation and their combinations
+//!
+//! Spaced repetition with Leitner boxes, so that kinds of exercises the learner fails come up more often than the ones they already master
+
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::equations::{
+    EquationExercise, EquationExerciseBuilder, EquationExerciseType, EquationVariable,
+};
+
+/// Amount of Leitner boxes, every box is drawn half as often as the one before it
+pub const BOXES: u8 = 5;
+
+/// Type of exercise together with the variable asked in it, e.g. "Combined / Current"
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ExerciseKind {
+    /// Type of the exercise
+    pub exercise_type: EquationExerciseType,
+    /// Variable the learner must solve
+    pub missing_variable: EquationVariable,
+}
+
+impl From<&EquationExercise> for ExerciseKind {
+    fn from(exercise: &EquationExercise) -> Self {
+        ExerciseKind {
+            exercise_type: exercise.exercise_type,
+            missing_variable: exercise.missing_variable,
+        }
+    }
+}
+
+/// Box a kind of exercise is currently in
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct LeitnerBox {
+    /// Kind of the exercises
+    #[serde(flatten)]
+    pub kind: ExerciseKind,
+    /// Box from 0, where unseen and failed kinds are, to BOXES - 1
+    pub level: u8,
+}
+
+/// Leitner boxes of one learner, kinds that have never been answered are in the first box
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(transparent)]
+pub struct Scheduler {
+    boxes: Vec<LeitnerBox>,
+}
+
+impl Scheduler {
+    /// Box of the kind
+    pub fn level(&self, kind: ExerciseKind) -> u8 {
+        self.boxes
+            .iter()
+            .find(|leitner_box| leitner_box.kind == kind)
+            .map_or(0, |leitner_box| leitner_box.level)
+    }
+
+    /// Relative probability of drawing the kind next
+    pub fn weight(&self, kind: ExerciseKind) -> u32 {
+        1 << (BOXES - 1 - self.level(kind))
+    }
+
+    /// Moves the kind one box up after a correct answer and back to the first box after a wrong one
+    pub fn record(&mut self, kind: ExerciseKind, correct: bool) {
+        let level = match correct {
+            true => (self.level(kind) + 1).min(BOXES - 1),
+            false => 0,
+        };
+        match self
+            .boxes
+            .iter_mut()
+            .find(|leitner_box| leitner_box.kind == kind)
+        {
+            Some(leitner_box) => leitner_box.level = level,
+            None => self.boxes.push(LeitnerBox { kind, level }),
+        }
+    }
+
+    /// Boxes of the kinds answered so far
+    pub fn boxes(&self) -> &[LeitnerBox] {
+        &self.boxes
+    }
+
+    /// Builds the next exercise, choosing among the kinds the builder allows by their boxes
+    pub fn next_exercise(&self, builder: &mut EquationExerciseBuilder) -> Result<EquationExercise> {
+        builder.build_weighted(|exercise_type, missing_variable| {
+            self.weight(ExerciseKind {
+                exercise_type,
+                missing_variable,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMBINED_CURRENT: ExerciseKind = ExerciseKind {
+        exercise_type: EquationExerciseType::Combined,
+        missing_variable: EquationVariable::Current,
+    };
+
+    #[test]
+    fn test_boxes() {
+        let mut scheduler = Scheduler::default();
+        assert_eq!(scheduler.level(COMBINED_CURRENT), 0);
+        assert_eq!(scheduler.weight(COMBINED_CURRENT), 16);
+
+        for _ in 0..10 {
+            scheduler.record(COMBINED_CURRENT, true);
+        }
+        assert_eq!(scheduler.level(COMBINED_CURRENT), BOXES - 1);
+        assert_eq!(scheduler.weight(COMBINED_CURRENT), 1);
+        assert_eq!(scheduler.boxes().len(), 1);
+
+        scheduler.record(COMBINED_CURRENT, false);
+        assert_eq!(scheduler.level(COMBINED_CURRENT), 0);
+    }
+
+    #[test]
+    fn test_failed_kinds_come_up_more_often() {
+        let mut scheduler = Scheduler::default();
+        let mut builder = EquationExerciseBuilder::new().set_seed(1);
+        // master everything but one kind
+        for exercise_type in [
+            EquationExerciseType::OhmsLaw,
+            EquationExerciseType::Power,
+            EquationExerciseType::Combined,
+        ] {
+            for missing_variable in exercise_type.missing_variables() {
+                let kind = ExerciseKind {
+                    exercise_type,
+                    missing_variable: *missing_variable,
+                };
+                for _ in 0..BOXES {
+                    scheduler.record(kind, kind != COMBINED_CURRENT);
+                }
+            }
+        }
+
+        let drawn = (0..200)
+            .map(|_| scheduler.next_exercise(&mut builder).unwrap())
+            .filter(|exercise| ExerciseKind::from(exercise) == COMBINED_CURRENT)
+            .count();
+        // 16 out of 16 + 8 * 1 on average
+        assert!(drawn > 80, "drawn {} times", drawn);
+    }
+}
@@ -0,0 +1,198 @@
+#![warn(missing_docs)]
+
+//! PUImURI trainer for Ohms law, power equation and their combinations
+//!
+//! Partial credit scoring of graded answers for quizzes where right or wrong is too blunt
+
+use serde::{Deserialize, Serialize};
+
+use crate::answer::ParsedAnswer;
+use crate::grading::{Grade, Verdict};
+use crate::mistakes::Mistake;
+
+/// Why an answer got the credit it got
+#[derive(Copy, Clone, Debug, Serialize, PartialEq, Eq)]
+pub enum ScoreReason {
+    /// Answer is correct
+    Correct,
+    /// Value is right but given in a wrong prefix
+    WrongPrefix,
+    /// Value is right but given in a wrong unit
+    WrongUnit,
+    /// Answer is close to the correct one, so the method was right but the arithmetic slipped
+    ArithmeticSlip,
+    /// Answer is the correct intermediate result of a multi-step problem, e.g. I^2 = P / R when I was asked
+    CorrectIntermediate,
+    /// Answer comes from a mistaken formula
+    Mistake(Mistake),
+    /// Answer is wrong
+    Incorrect,
+}
+
+/// Credit given for an answer
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct Score {
+    /// Credit between 0 and 1
+    pub value: f64,
+    /// Why this credit was given
+    pub reasons: Vec<ScoreReason>,
+}
+
+/// Credit given for each kind of partially correct answer
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ScoringModel {
+    /// Credit for the right value in a wrong prefix
+    pub wrong_prefix: f64,
+    /// Credit for the right value in a wrong unit
+    pub wrong_unit: f64,
+    /// Credit for an answer within the slip tolerance
+    pub arithmetic_slip: f64,
+    /// Credit for the correct intermediate result of a multi-step problem
+    pub correct_intermediate: f64,
+    /// Relative error that still counts as an arithmetic slip, e.g. 0.1 for 10 %
+    pub slip_tolerance: f64,
+}
+
+impl Default for ScoringModel {
+    fn default() -> Self {
+        ScoringModel {
+            wrong_prefix: 0.5,
+            wrong_unit: 0.5,
+            arithmetic_slip: 0.5,
+            correct_intermediate: 0.5,
+            slip_tolerance: 0.1,
+        }
+    }
+}
+
+impl ScoringModel {
+    /// Scores the graded answer
+    pub fn score(&self, grade: &Grade, answer: &ParsedAnswer) -> Score {
+        let (value, reason) = match &grade.verdict {
+            Verdict::Correct => (1.0, ScoreReason::Correct),
+            Verdict::WrongPrefix { .. } => (self.wrong_prefix, ScoreReason::WrongPrefix),
+            Verdict::WrongUnit { .. } => (self.wrong_unit, ScoreReason::WrongUnit),
+            Verdict::Incorrect {
+                diagnosis: Some(diagnosis),
+            } => match diagnosis.mistake {
+                // the square root is the last step, so everything before it was right
+                Mistake::MissingSquareRoot => {
+                    (self.correct_intermediate, ScoreReason::CorrectIntermediate)
+                }
+                mistake => (0.0, ScoreReason::Mistake(mistake)),
+            },
+            Verdict::Incorrect { diagnosis: None } => {
+                let expected = grade.solution.answer;
+                let given = answer.to_base(grade.solution.prefix);
+                let error = if expected == 0.0 {
+                    given.abs()
+                } else {
+                    ((given - expected) / expected).abs()
+                };
+                // a value in another unit is a different quantity, not a slip
+                let same_unit = answer.unit.is_none_or(|unit| unit == grade.solution.unit);
+                if same_unit && error <= self.slip_tolerance {
+                    (self.arithmetic_slip, ScoreReason::ArithmeticSlip)
+                } else {
+                    (0.0, ScoreReason::Incorrect)
+                }
+            }
+        };
+
+        Score {
+            value: value.clamp(0.0, 1.0),
+            reasons: vec![reason],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equations::{EquationExercise, EquationExerciseType, EquationVariable};
+
+    fn score(exercise: &EquationExercise, answer: &str) -> Score {
+        let answer = ParsedAnswer::parse(answer).unwrap();
+        let grade = exercise.grade(&answer, None).unwrap();
+        ScoringModel::default().score(&grade, &answer)
+    }
+
+    fn combined_current() -> EquationExercise {
+        EquationExercise {
+            id: String::new(),
+            token: None,
+            exercise_type: EquationExerciseType::Combined,
+            missing_variable: EquationVariable::Current,
+            given_variables: vec![
+                (EquationVariable::Power, 100.0),
+                (EquationVariable::Resistance, 4.0),
+            ],
+            prefixes: vec![],
+            correct_answer: None,
+        }
+    }
+
+    #[test]
+    fn test_score_full_and_no_credit() {
+        let exercise = combined_current();
+        let full = score(&exercise, "5");
+        assert_eq!(full.value, 1.0);
+        assert_eq!(full.reasons, vec![ScoreReason::Correct]);
+
+        let none = score(&exercise, "12");
+        assert_eq!(none.value, 0.0);
+        assert_eq!(none.reasons, vec![ScoreReason::Incorrect]);
+    }
+
+    #[test]
+    fn test_score_partial_credit() {
+        let exercise = combined_current();
+        assert_eq!(
+            score(&exercise, "5000").reasons,
+            vec![ScoreReason::WrongPrefix]
+        );
+        assert_eq!(
+            score(&exercise, "5 V").reasons,
+            vec![ScoreReason::WrongUnit]
+        );
+        assert_eq!(score(&exercise, "5.3").value, 0.5);
+        assert_eq!(
+            score(&exercise, "5.3").reasons,
+            vec![ScoreReason::ArithmeticSlip]
+        );
+        assert_eq!(
+            score(&exercise, "5.3 A").reasons,
+            vec![ScoreReason::ArithmeticSlip]
+        );
+        let wrong_unit = score(&exercise, "5.3 V");
+        assert_eq!(wrong_unit.value, 0.0);
+        assert_eq!(wrong_unit.reasons, vec![ScoreReason::Incorrect]);
+        assert_eq!(
+            score(&exercise, "25").reasons,
+            vec![ScoreReason::CorrectIntermediate]
+        );
+    }
+
+    #[test]
+    fn test_score_mistake() {
+        let exercise = combined_current();
+        let score = score(&exercise, "0.2");
+        assert_eq!(score.value, 0.0);
+        assert_eq!(
+            score.reasons,
+            vec![ScoreReason::Mistake(Mistake::InvertedDivision)]
+        );
+    }
+
+    #[test]
+    fn test_custom_scoring_model() {
+        let exercise = combined_current();
+        let answer = ParsedAnswer::parse("5000").unwrap();
+        let grade = exercise.grade(&answer, None).unwrap();
+        let model = ScoringModel {
+            wrong_prefix: 0.75,
+            ..ScoringModel::default()
+        };
+        assert_eq!(model.score(&grade, &answer).value, 0.75);
+    }
+}
@@ -0,0 +1,457 @@
+#![warn(missing_docs)]
+
+//! PUImURI trainer for Ohms law, power equation and their combinations
+//!
+//! Training sessions that keep track of issued exercises, recorded answers, score and streaks
+
+use eyre::Result;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+
+use crate::adaptive::AdaptiveDifficulty;
+use crate::equations::{EquationExercise, EquationExerciseType, EquationVariable};
+use crate::grading::Grade;
+use crate::progress::Progress;
+use crate::scheduler::{ExerciseKind, Scheduler};
+use crate::scoring::Score;
+use crate::spec::ExerciseSpec;
+use crate::TrainerError;
+
+/// How many unanswered exercises a session remembers, older ones can no longer be answered
+const MAX_ISSUED: usize = 100;
+
+/// Seconds a session may go unused before it expires
+pub const SESSION_TTL: u64 = 7 * 24 * 60 * 60;
+
+/// Seed of the n:th exercise of a seeded session, mixed so that sessions with neighbouring seeds do not share exercises
+fn exercise_seed(seed: u64, index: u64) -> u64 {
+    let digest = Sha256::new()
+        .chain_update(seed.to_le_bytes())
+        .chain_update(index.to_le_bytes())
+        .finalize();
+    u64::from_le_bytes(digest[..8].try_into().unwrap())
+}
+
+/// Answer recorded in a session
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Attempt {
+    /// Fingerprint of the answered exercise
+    pub exercise_id: String,
+    /// Type of the answered exercise
+    pub exercise_type: EquationExerciseType,
+    /// Variable that was asked
+    pub missing_variable: EquationVariable,
+    /// Whether the answer was fully correct
+    pub correct: bool,
+    /// Partial credit between 0 and 1
+    pub score: f64,
+    /// When the answer was given, in seconds since the Unix epoch
+    pub answered_at: u64,
+}
+
+/// Accuracy of the answers to one type of exercise
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct TypeStats {
+    /// Type of the exercises
+    pub exercise_type: EquationExerciseType,
+    /// How many were answered
+    pub answered: usize,
+    /// How many were answered correctly
+    pub correct: usize,
+    /// Share of correct answers between 0 and 1
+    pub accuracy: f64,
+}
+
+/// Summary of a session
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct SessionStats {
+    /// How many exercises were answered
+    pub answered: usize,
+    /// How many were answered correctly
+    pub correct: usize,
+    /// Sum of the partial credit of every answer
+    pub score: f64,
+    /// Share of correct answers between 0 and 1
+    pub accuracy: f64,
+    /// Correct answers in a row at the end of the session
+    pub streak: usize,
+    /// Longest run of correct answers in the session
+    pub best_streak: usize,
+    /// Accuracy per exercise type, only types that were answered
+    pub per_type: Vec<TypeStats>,
+    /// Seconds from the start of the session to the latest activity
+    pub time_spent: u64,
+}
+
+/// Share of correct answers, zero when nothing was answered
+fn accuracy(correct: usize, answered: usize) -> f64 {
+    if answered == 0 {
+        0.0
+    } else {
+        correct as f64 / answered as f64
+    }
+}
+
+/// Training session of one learner
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TrainingSession {
+    /// Random identifier of the session
+    pub id: String,
+    /// Identifier of the user the session belongs to, None for anonymous sessions
+    #[serde(default)]
+    pub user_id: Option<String>,
+    /// Settings of the exercises issued in the session
+    pub spec: ExerciseSpec,
+    /// When the session started, in seconds since the Unix epoch
+    pub started_at: u64,
+    /// When the session was last used, in seconds since the Unix epoch
+    pub last_activity: u64,
+    /// How many exercises have been issued in the session
+    #[serde(default)]
+    pub issued_count: u64,
+    /// Fingerprints of issued exercises that have not been answered yet
+    #[serde(default)]
+    pub issued: VecDeque<String>,
+    /// Recorded answers, oldest first
+    #[serde(default)]
+    pub attempts: Vec<Attempt>,
+    /// Leitner boxes that decide which kind of exercise is issued next
+    #[serde(default)]
+    pub scheduler: Scheduler,
+    /// Difficulty level that follows the recent answers, used for adaptive exercises
+    #[serde(default)]
+    pub adaptive: AdaptiveDifficulty,
+}
+
+impl TrainingSession {
+    /// Starts a new session with a random identifier
+    pub fn new(spec: ExerciseSpec, now: u64) -> TrainingSession {
+        TrainingSession {
+            adaptive: AdaptiveDifficulty::new(spec.difficulty.unwrap_or_default()),
+            id: format!("{:032x}", rand::thread_rng().gen::<u128>()),
+            user_id: None,
+            spec,
+            started_at: now,
+            last_activity: now,
+            issued_count: 0,
+            issued: VecDeque::new(),
+            attempts: Vec::new(),
+            scheduler: Scheduler::default(),
+        }
+    }
+
+    /// Continues from the progress of the user instead of empty Leitner boxes and the requested difficulty level
+    pub fn resume(&mut self, progress: &Progress) {
+        self.user_id = Some(progress.user_id.clone());
+        self.scheduler = progress.scheduler.clone();
+        if let Some(adaptive) = &progress.adaptive {
+            self.adaptive = adaptive.clone();
+        }
+    }
+
+    /// Whether the session has gone unused for longer than SESSION_TTL
+    pub fn is_expired(&self, now: u64) -> bool {
+        now.saturating_sub(self.last_activity) > SESSION_TTL
+    }
+
+    /// Generates and issues the next exercise of the session, focusing on the kinds of exercises the learner fails
+    ///
+    /// A seeded session continues its own sequence, so the n:th exercise of every session with the same seed and answers is the same.
+    pub fn next_exercise(&mut self, now: u64) -> Result<EquationExercise> {
+        let spec = self.spec.clone();
+        self.next_exercise_with(spec, now)
+    }
+
+    /// Generates and issues an exercise with the settings, but on the difficulty level the recent answers of the session call for
+    pub fn next_adaptive_exercise(
+        &mut self,
+        spec: &ExerciseSpec,
+        now: u64,
+    ) -> Result<EquationExercise> {
+        let spec = ExerciseSpec {
+            difficulty: Some(self.adaptive.level),
+            ..spec.clone()
+        };
+        self.next_exercise_with(spec, now)
+    }
+
+    fn next_exercise_with(&mut self, mut spec: ExerciseSpec, now: u64) -> Result<EquationExercise> {
+        spec.seed = spec.seed.map(|seed| exercise_seed(seed, self.issued_count));
+        let exercise = self.scheduler.next_exercise(&mut spec.builder()?)?;
+        self.issue(&exercise, now);
+        Ok(exercise)
+    }
+
+    /// Remembers that the exercise was handed out in this session so that it can be answered once
+    pub fn issue(&mut self, exercise: &EquationExercise, now: u64) {
+        self.issued_count += 1;
+        self.issued.push_back(exercise.fingerprint());
+        if self.issued.len() > MAX_ISSUED {
+            self.issued.pop_front();
+        }
+        self.last_activity = now;
+    }
+
+    /// Records the graded answer of an exercise issued in this session
+    pub fn record(
+        &mut self,
+        exercise: &EquationExercise,
+        grade: &Grade,
+        score: &Score,
+        now: u64,
+    ) -> Result<&Attempt, TrainerError> {
+        let exercise_id = exercise.fingerprint();
+        let position = self
+            .issued
+            .iter()
+            .position(|issued| *issued == exercise_id)
+            .ok_or_else(|| TrainerError::ExerciseNotIssued(exercise_id.clone()))?;
+        self.issued.remove(position);
+        self.scheduler
+            .record(ExerciseKind::from(exercise), grade.is_correct());
+        self.adaptive.record(grade.is_correct());
+
+        self.attempts.push(Attempt {
+            exercise_id,
+            exercise_type: exercise.exercise_type,
+            missing_variable: exercise.missing_variable,
+            correct: grade.is_correct(),
+            score: score.value,
+            answered_at: now,
+        });
+        self.last_activity = now;
+        Ok(self.attempts.last().unwrap())
+    }
+
+    /// Current score, streak and accuracy of the session
+    pub fn stats(&self) -> SessionStats {
+        let answered = self.attempts.len();
+        let correct = self
+            .attempts
+            .iter()
+            .filter(|attempt| attempt.correct)
+            .count();
+
+        let mut streak = 0;
+        let mut best_streak = 0;
+        for attempt in &self.attempts {
+            streak = if attempt.correct { streak + 1 } else { 0 };
+            best_streak = best_streak.max(streak);
+        }
+
+        let per_type = [
+            EquationExerciseType::OhmsLaw,
+            EquationExerciseType::Power,
+            EquationExerciseType::Combined,
+        ]
+        .into_iter()
+        .filter_map(|exercise_type| {
+            let attempts = self
+                .attempts
+                .iter()
+                .filter(|attempt| attempt.exercise_type == exercise_type);
+            let answered = attempts.clone().count();
+            let correct = attempts.filter(|attempt| attempt.correct).count();
+            (answered > 0).then(|| TypeStats {
+                exercise_type,
+                answered,
+                correct,
+                accuracy: accuracy(correct, answered),
+            })
+        })
+        .collect();
+
+        SessionStats {
+            answered,
+            correct,
+            score: self
+                .attempts
+                .iter()
+                .fold(0.0, |score, attempt| score + attempt.score),
+            accuracy: accuracy(correct, answered),
+            streak,
+            best_streak,
+            per_type,
+            time_spent: self.last_activity.saturating_sub(self.started_at),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::answer::ParsedAnswer;
+    use crate::difficulty::Difficulty;
+    use crate::equations::EquationExerciseBuilder;
+    use crate::scoring::ScoringModel;
+
+    /// Issues an exercise of the type and answers it either right or wrong
+    fn answer(
+        session: &mut TrainingSession,
+        exercise_type: EquationExerciseType,
+        right: bool,
+        now: u64,
+    ) {
+        let exercise = EquationExerciseBuilder::new()
+            .set_type(exercise_type)
+            .build()
+            .unwrap();
+        session.issue(&exercise, now);
+        let correct = exercise.solve().unwrap().answer;
+        let answer = ParsedAnswer::from(if right { correct } else { correct * 3.0 });
+        let grade = exercise.grade(&answer, None).unwrap();
+        let score = ScoringModel::default().score(&grade, &answer);
+        session.record(&exercise, &grade, &score, now).unwrap();
+    }
+
+    #[test]
+    fn test_session_stats() {
+        let mut session = TrainingSession::new(ExerciseSpec::default(), 1000);
+        assert_eq!(session.stats().answered, 0);
+        assert_eq!(session.stats().accuracy, 0.0);
+        assert!(session.stats().score.is_sign_positive());
+
+        answer(&mut session, EquationExerciseType::OhmsLaw, true, 1010);
+        answer(&mut session, EquationExerciseType::OhmsLaw, true, 1020);
+        answer(&mut session, EquationExerciseType::Power, false, 1030);
+        answer(&mut session, EquationExerciseType::Power, true, 1040);
+
+        let stats = session.stats();
+        assert_eq!(stats.answered, 4);
+        assert_eq!(stats.correct, 3);
+        assert_eq!(stats.accuracy, 0.75);
+        assert_eq!(stats.score, 3.0);
+        assert_eq!(stats.streak, 1);
+        assert_eq!(stats.best_streak, 2);
+        assert_eq!(stats.time_spent, 40);
+        assert_eq!(stats.per_type.len(), 2);
+        assert_eq!(
+            stats.per_type[0].exercise_type,
+            EquationExerciseType::OhmsLaw
+        );
+        assert_eq!(stats.per_type[0].accuracy, 1.0);
+        assert_eq!(stats.per_type[1].accuracy, 0.5);
+    }
+
+    #[test]
+    fn test_record_only_issued_exercises_once() {
+        let mut session = TrainingSession::new(ExerciseSpec::default(), 0);
+        let exercise = EquationExerciseBuilder::new()
+            .build_with_random_exercisetype()
+            .unwrap();
+        let answer = ParsedAnswer::from(exercise.solve().unwrap().answer);
+        let grade = exercise.grade(&answer, None).unwrap();
+        let score = ScoringModel::default().score(&grade, &answer);
+
+        assert!(matches!(
+            session.record(&exercise, &grade, &score, 1),
+            Err(TrainerError::ExerciseNotIssued(_))
+        ));
+        session.issue(&exercise, 1);
+        assert!(
+            session
+                .record(&exercise, &grade, &score, 2)
+                .unwrap()
+                .correct
+        );
+        assert!(session.record(&exercise, &grade, &score, 3).is_err());
+    }
+
+    #[test]
+    fn test_answers_move_kinds_between_boxes() {
+        let mut session = TrainingSession::new(ExerciseSpec::default(), 0);
+        let exercise = session.next_exercise(0).unwrap();
+        let kind = ExerciseKind::from(&exercise);
+        let answer = ParsedAnswer::from(exercise.solve().unwrap().answer);
+        let grade = exercise.grade(&answer, None).unwrap();
+        let score = ScoringModel::default().score(&grade, &answer);
+        session.record(&exercise, &grade, &score, 1).unwrap();
+        assert_eq!(session.scheduler.level(kind), 1);
+
+        let exercise = session.next_exercise(2).unwrap();
+        let answer = ParsedAnswer::from(exercise.solve().unwrap().answer * 3.0);
+        let grade = exercise.grade(&answer, None).unwrap();
+        let score = ScoringModel::default().score(&grade, &answer);
+        session.record(&exercise, &grade, &score, 3).unwrap();
+        assert_eq!(session.scheduler.level(ExerciseKind::from(&exercise)), 0);
+    }
+
+    #[test]
+    fn test_adaptive_exercises_follow_answers() {
+        let spec = ExerciseSpec {
+            difficulty: Some(Difficulty::MIN),
+            ..ExerciseSpec::default()
+        };
+        let mut session = TrainingSession::new(spec.clone(), 0);
+        for now in 0..20 {
+            let exercise = session.next_adaptive_exercise(&spec, now).unwrap();
+            let answer = ParsedAnswer::from(exercise.solve().unwrap().answer);
+            let grade = exercise.grade(&answer, None).unwrap();
+            let score = ScoringModel::default().score(&grade, &answer);
+            session.record(&exercise, &grade, &score, now).unwrap();
+        }
+        assert_eq!(session.adaptive.level.level(), 5);
+
+        // the settings of the session itself are left as they were
+        let exercise = session.next_exercise(20).unwrap();
+        assert_eq!(exercise.exercise_type, EquationExerciseType::OhmsLaw);
+        let exercise = (0..30)
+            .map(|_| session.next_adaptive_exercise(&spec, 20).unwrap())
+            .find(|exercise| exercise.exercise_type == EquationExerciseType::Combined);
+        assert!(exercise.is_some());
+    }
+
+    #[test]
+    fn test_sessions_get_different_ids() {
+        let first = TrainingSession::new(ExerciseSpec::default(), 0);
+        let second = TrainingSession::new(ExerciseSpec::default(), 0);
+        assert_eq!(first.id.len(), 32);
+        assert_ne!(first.id, second.id);
+    }
+
+    #[test]
+    fn test_seeded_session_sequence() {
+        let spec = ExerciseSpec {
+            seed: Some(9),
+            ..ExerciseSpec::default()
+        };
+        let mut first = TrainingSession::new(spec.clone(), 0);
+        let mut second = TrainingSession::new(spec, 0);
+        let sequence: Vec<_> = (0..5).map(|_| first.next_exercise(0).unwrap()).collect();
+        for exercise in &sequence {
+            assert_eq!(*exercise, second.next_exercise(0).unwrap());
+        }
+        assert!(sequence.iter().any(|exercise| *exercise != sequence[0]));
+        assert_eq!(first.issued_count, 5);
+        assert_eq!(first.issued.len(), 5);
+    }
+
+    #[test]
+    fn test_session_expires() {
+        let mut session = TrainingSession::new(ExerciseSpec::default(), 0);
+        assert!(!session.is_expired(SESSION_TTL));
+        assert!(session.is_expired(SESSION_TTL + 1));
+        session.next_exercise(SESSION_TTL).unwrap();
+        assert!(!session.is_expired(SESSION_TTL + 1));
+    }
+
+    #[test]
+    fn test_neighbouring_seeds_do_not_overlap() {
+        let sequence = |seed: u64| {
+            let mut session = TrainingSession::new(
+                ExerciseSpec {
+                    seed: Some(seed),
+                    ..ExerciseSpec::default()
+                },
+                0,
+            );
+            (0..5)
+                .map(|_| session.next_exercise(0).unwrap())
+                .collect::<Vec<_>>()
+        };
+        // with the seed offset by the index the second session would repeat the first one shifted by one
+        assert_ne!(sequence(9)[1..], sequence(10)[..4]);
+    }
+}
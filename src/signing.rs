@@ -0,0 +1,137 @@
+#![warn(missing_docs)]
+
+//! PUImURI trainer for Ohms law, power equation and their combinations
+//!
+//! HMAC signed exercise tokens so that clients can not answer exercises they made up themselves
+
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+
+use crate::equations::EquationExercise;
+use crate::TrainerError;
+
+/// Length of random keys, configured keys shorter than this are weak
+pub const KEY_LENGTH: usize = 32;
+
+/// Signs exercises handed out by the server and verifies them when they come back
+#[derive(Clone)]
+pub struct ExerciseSigner {
+    key: Vec<u8>,
+}
+
+impl ExerciseSigner {
+    /// Creates a signer with the given secret key
+    pub fn new(key: &[u8]) -> ExerciseSigner {
+        ExerciseSigner { key: key.to_vec() }
+    }
+
+    /// Creates a signer with a random key, tokens signed with it are only valid until the signer is dropped
+    pub fn random() -> ExerciseSigner {
+        let mut key = [0u8; KEY_LENGTH];
+        rand::thread_rng().fill_bytes(&mut key);
+        ExerciseSigner::new(&key)
+    }
+
+    /// Returns a MAC over the content of the exercise and the session it was issued in
+    fn mac(&self, exercise: &EquationExercise, session_id: Option<&str>) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(exercise.canonical().as_bytes());
+        if let Some(session_id) = session_id {
+            // the canonical form never contains a NUL byte, so no free exercise signs the same bytes
+            mac.update(b"\0session\0");
+            mac.update(session_id.as_bytes());
+        }
+        mac
+    }
+
+    /// Sets the token of the exercise, an exercise issued in a session can only be answered in that session
+    pub fn sign(&self, exercise: &mut EquationExercise, session_id: Option<&str>) {
+        let mac = self.mac(exercise, session_id);
+        exercise.token = Some(hex::encode(mac.finalize().into_bytes()));
+    }
+
+    /// Checks that the exercise carries a token signed with this key for the session and that it has not been modified
+    /// since
+    pub fn verify(
+        &self,
+        exercise: &EquationExercise,
+        session_id: Option<&str>,
+    ) -> Result<(), TrainerError> {
+        let token = exercise.token.as_ref().ok_or(TrainerError::InvalidToken)?;
+        let signature = hex::decode(token).map_err(|_| TrainerError::InvalidToken)?;
+        self.mac(exercise, session_id)
+            .verify_slice(&signature)
+            .map_err(|_| TrainerError::InvalidToken)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equations::{EquationExerciseBuilder, EquationVariable};
+
+    fn signed_exercise(signer: &ExerciseSigner) -> EquationExercise {
+        let mut exercise = EquationExerciseBuilder::new()
+            .build_with_random_exercisetype()
+            .unwrap();
+        signer.sign(&mut exercise, None);
+        exercise
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let signer = ExerciseSigner::new(b"secret");
+        let exercise = signed_exercise(&signer);
+        assert!(signer.verify(&exercise, None).is_ok());
+
+        // the token survives the round trip through the client
+        let json = serde_json::to_string(&exercise).unwrap();
+        let returned: EquationExercise = serde_json::from_str(&json).unwrap();
+        assert!(signer.verify(&returned, None).is_ok());
+    }
+
+    #[test]
+    fn test_reject_modified_exercise() {
+        let signer = ExerciseSigner::new(b"secret");
+        let exercise = signed_exercise(&signer);
+
+        let mut easier = exercise.clone();
+        easier.given_variables = vec![
+            (EquationVariable::Voltage, 1.0),
+            (EquationVariable::Resistance, 1.0),
+        ];
+        assert!(matches!(
+            signer.verify(&easier, None),
+            Err(TrainerError::InvalidToken)
+        ));
+
+        let mut unsigned = exercise.clone();
+        unsigned.token = None;
+        assert!(signer.verify(&unsigned, None).is_err());
+
+        let mut garbage = exercise.clone();
+        garbage.token = Some("not hex".to_string());
+        assert!(signer.verify(&garbage, None).is_err());
+
+        assert!(ExerciseSigner::new(b"other")
+            .verify(&exercise, None)
+            .is_err());
+        assert!(ExerciseSigner::random().verify(&exercise, None).is_err());
+    }
+
+    #[test]
+    fn test_session_exercises_stay_in_their_session() {
+        let signer = ExerciseSigner::new(b"secret");
+        let mut exercise = signed_exercise(&signer);
+        signer.sign(&mut exercise, Some("session"));
+        assert!(signer.verify(&exercise, Some("session")).is_ok());
+        assert!(signer.verify(&exercise, Some("another")).is_err());
+        assert!(signer.verify(&exercise, None).is_err());
+
+        // nor can a free exercise be answered in a session
+        signer.sign(&mut exercise, None);
+        assert!(signer.verify(&exercise, Some("session")).is_err());
+    }
+}
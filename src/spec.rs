@@ -0,0 +1,272 @@
+#![warn(missing_docs)]
+
+//! PUImURI trainer for Ohms law, power equation and their combinations
+//!
+//! Description of the exercises a client asks for, e.g. from the query string of the REST interface
+
+use eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::difficulty::Difficulty;
+use crate::equations::{
+    EquationExercise, EquationExerciseBuilder, EquationExerciseType, EquationVariable,
+};
+use crate::TrainerError;
+
+/// Largest amount of exercises generated or graded at once
+pub const MAX_SET_SIZE: usize = 200;
+
+/// How many exercises may be drawn per requested exercise while looking for ones not in the set yet
+const UNIQUE_ATTEMPTS_PER_EXERCISE: usize = 100;
+
+/// Settings for generating exercises, every setting is optional and unset ones keep the builder defaults
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct ExerciseSpec {
+    /// Type of the exercise, any type of the difficulty level if not set
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub exercise_type: Option<EquationExerciseType>,
+    /// Variable the user must solve, any variable of the type if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub missing: Option<EquationVariable>,
+    /// Difficulty level, applied before the other settings so they can override its ranges
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<Difficulty>,
+    /// Seed for reproducible exercises
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Smallest voltage in volts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voltage_min: Option<f64>,
+    /// Largest voltage in volts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voltage_max: Option<f64>,
+    /// Smallest current in amperes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_min: Option<f64>,
+    /// Largest current in amperes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_max: Option<f64>,
+    /// Smallest resistance in ohms
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resistance_min: Option<f64>,
+    /// Largest resistance in ohms
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resistance_max: Option<f64>,
+    /// Smallest power in watts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub power_min: Option<f64>,
+    /// Largest power in watts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub power_max: Option<f64>,
+}
+
+impl ExerciseSpec {
+    /// Configured minimum and maximum of the variable
+    fn range(&self, variable: EquationVariable) -> (Option<f64>, Option<f64>) {
+        match variable {
+            EquationVariable::Voltage => (self.voltage_min, self.voltage_max),
+            EquationVariable::Current => (self.current_min, self.current_max),
+            EquationVariable::Resistance => (self.resistance_min, self.resistance_max),
+            EquationVariable::Power => (self.power_min, self.power_max),
+        }
+    }
+
+    /// Creates a builder with the settings, call build_with_random_exercisetype() on it to get the exercises
+    pub fn builder(&self) -> Result<EquationExerciseBuilder> {
+        let mut builder = EquationExerciseBuilder::new();
+        if let Some(difficulty) = self.difficulty {
+            builder = builder.set_difficulty(difficulty);
+        }
+        if let Some(exercise_type) = self.exercise_type {
+            builder = builder.set_exercise_types(vec![exercise_type]);
+        }
+        builder = builder.set_missing_variable(self.missing);
+        if let Some(seed) = self.seed {
+            builder = builder.set_seed(seed);
+        }
+        for variable in [
+            EquationVariable::Voltage,
+            EquationVariable::Current,
+            EquationVariable::Resistance,
+            EquationVariable::Power,
+        ] {
+            let (min, max) = self.range(variable);
+            if min.is_none() && max.is_none() {
+                continue;
+            }
+            // a range given only partially keeps the other end of the current range
+            let (current_min, current_max) = builder.range(variable);
+            builder = builder.set_range(
+                variable,
+                min.unwrap_or(current_min),
+                max.unwrap_or(current_max),
+            )?;
+        }
+        Ok(builder)
+    }
+}
+
+/// Settings for generating a set of exercises at once, e.g. for a worksheet
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct ExerciseSetSpec {
+    /// How many exercises to generate, at most MAX_SET_SIZE
+    pub count: usize,
+    /// Types mixed in the set, overrides the type of the exercise settings
+    #[serde(default)]
+    pub types: Vec<EquationExerciseType>,
+    /// Never repeat the same exercise within the set
+    #[serde(default)]
+    pub unique: bool,
+    /// Settings of every exercise in the set
+    #[serde(flatten)]
+    pub exercise: ExerciseSpec,
+}
+
+impl ExerciseSetSpec {
+    /// Generates the set, the same seed always generates the same set
+    pub fn build(&self) -> Result<Vec<EquationExercise>> {
+        if self.count == 0 || self.count > MAX_SET_SIZE {
+            return Err(TrainerError::InvalidSetSize(self.count))
+                .with_context(|| format!("A set has 1 to {} exercises", MAX_SET_SIZE));
+        }
+        let mut builder = self.exercise.builder()?;
+        if !self.types.is_empty() {
+            builder = builder.set_exercise_types(self.types.clone());
+        }
+
+        let mut exercises = Vec::with_capacity(self.count);
+        let mut seen = HashSet::new();
+        for _ in 0..self.count * UNIQUE_ATTEMPTS_PER_EXERCISE {
+            let exercise = builder.build_with_random_exercisetype()?;
+            if self.unique && !seen.insert(exercise.id.clone()) {
+                continue;
+            }
+            exercises.push(exercise);
+            if exercises.len() == self.count {
+                return Ok(exercises);
+            }
+        }
+        Err(TrainerError::InvalidSetSize(self.count)).with_context(|| {
+            format!(
+                "Only {} different exercises could be generated with the settings",
+                exercises.len()
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_builds_requested_exercises() {
+        let spec = ExerciseSpec {
+            exercise_type: Some(EquationExerciseType::Combined),
+            missing: Some(EquationVariable::Current),
+            resistance_min: Some(10.0),
+            resistance_max: Some(20.0),
+            power_max: Some(100.0),
+            ..ExerciseSpec::default()
+        };
+        let mut builder = spec.builder().unwrap();
+        assert_eq!(builder.range(EquationVariable::Power), (1.0, 100.0));
+        for _ in 0..20 {
+            let exercise = builder.build_with_random_exercisetype().unwrap();
+            assert_eq!(exercise.exercise_type, EquationExerciseType::Combined);
+            assert_eq!(exercise.missing_variable, EquationVariable::Current);
+            let resistance = exercise.given_value(EquationVariable::Resistance).unwrap();
+            assert!((10.0..=20.0).contains(&resistance));
+        }
+    }
+
+    #[test]
+    fn test_spec_ranges_override_difficulty() {
+        let spec = ExerciseSpec {
+            difficulty: Some(Difficulty::MIN),
+            voltage_max: Some(12.0),
+            ..ExerciseSpec::default()
+        };
+        let builder = spec.builder().unwrap();
+        assert_eq!(
+            builder.range(EquationVariable::Voltage),
+            (Difficulty::MIN.range(EquationVariable::Voltage).0, 12.0)
+        );
+    }
+
+    #[test]
+    fn test_spec_invalid_range() {
+        let spec = ExerciseSpec {
+            current_min: Some(5.0),
+            current_max: Some(1.0),
+            ..ExerciseSpec::default()
+        };
+        assert!(matches!(
+            spec.builder().unwrap_err().downcast_ref::<TrainerError>(),
+            Some(TrainerError::MinLargerThanMax)
+        ));
+    }
+
+    fn set_spec(count: usize, seed: u64) -> ExerciseSetSpec {
+        ExerciseSetSpec {
+            count,
+            exercise: ExerciseSpec {
+                seed: Some(seed),
+                ..ExerciseSpec::default()
+            },
+            ..ExerciseSetSpec::default()
+        }
+    }
+
+    #[test]
+    fn test_build_set() {
+        let exercises = set_spec(25, 1).build().unwrap();
+        assert_eq!(exercises.len(), 25);
+        assert_eq!(exercises, set_spec(25, 1).build().unwrap());
+        assert_ne!(exercises, set_spec(25, 2).build().unwrap());
+
+        assert!(set_spec(0, 1).build().is_err());
+        assert!(set_spec(MAX_SET_SIZE + 1, 1).build().is_err());
+    }
+
+    #[test]
+    fn test_build_set_type_mix() {
+        let spec = ExerciseSetSpec {
+            types: vec![EquationExerciseType::OhmsLaw, EquationExerciseType::Power],
+            ..set_spec(30, 3)
+        };
+        let exercises = spec.build().unwrap();
+        assert!(exercises
+            .iter()
+            .all(|exercise| exercise.exercise_type != EquationExerciseType::Combined));
+        assert!(exercises
+            .iter()
+            .any(|exercise| exercise.exercise_type == EquationExerciseType::Power));
+    }
+
+    #[test]
+    fn test_build_unique_set() {
+        // 2 * 2 currents and resistances give only four different exercises
+        let mut spec = ExerciseSetSpec {
+            unique: true,
+            ..set_spec(4, 4)
+        };
+        spec.exercise.exercise_type = Some(EquationExerciseType::OhmsLaw);
+        spec.exercise.missing = Some(EquationVariable::Voltage);
+        spec.exercise.current_min = Some(1.0);
+        spec.exercise.current_max = Some(2.0);
+        spec.exercise.resistance_min = Some(1.0);
+        spec.exercise.resistance_max = Some(2.0);
+
+        let exercises = spec.build().unwrap();
+        let ids: HashSet<_> = exercises.iter().map(|exercise| &exercise.id).collect();
+        assert_eq!(ids.len(), 4);
+
+        spec.count = 5;
+        assert!(matches!(
+            spec.build().unwrap_err().downcast_ref::<TrainerError>(),
+            Some(TrainerError::InvalidSetSize(5))
+        ));
+    }
+}
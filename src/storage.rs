@@ -0,0 +1,701 @@
+#![warn(missing_docs)]
+
+//! PUImURI trainer for Ohms law, power equation and their combinations
+//!
+//! SQLite storage of users, their progress, training sessions, exams, classes, issued exercises and attempts
+
+use eyre::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::accounts::{Login, User};
+use crate::classroom::Classroom;
+use crate::equations::EquationExercise;
+use crate::exam::Exam;
+use crate::grading::Grade;
+use crate::progress::Progress;
+use crate::scoring::Score;
+use crate::session::TrainingSession;
+
+/// Schema migrations, the n:th entry upgrades the database from user_version n to n + 1
+const MIGRATIONS: &[&str] = &[
+    r#"
+    CREATE TABLE users (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+    CREATE TABLE sessions (
+        id TEXT PRIMARY KEY,
+        user_id TEXT REFERENCES users(id),
+        started_at INTEGER NOT NULL,
+        last_activity INTEGER NOT NULL,
+        state TEXT NOT NULL
+    );
+    CREATE TABLE exercises (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        fingerprint TEXT NOT NULL,
+        session_id TEXT REFERENCES sessions(id),
+        exercise TEXT NOT NULL,
+        issued_at INTEGER NOT NULL
+    );
+    CREATE INDEX exercises_fingerprint ON exercises(fingerprint);
+    CREATE TABLE attempts (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        fingerprint TEXT NOT NULL,
+        session_id TEXT REFERENCES sessions(id),
+        exercise_type TEXT NOT NULL,
+        missing_variable TEXT NOT NULL,
+        answer TEXT NOT NULL,
+        verdict TEXT NOT NULL,
+        correct INTEGER NOT NULL,
+        score REAL NOT NULL,
+        answered_at INTEGER NOT NULL
+    );
+    CREATE INDEX attempts_session ON attempts(session_id);
+    "#,
+    r#"
+    ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'Student';
+    ALTER TABLE users ADD COLUMN password_hash TEXT NOT NULL DEFAULT '';
+    -- names differing only in case are told apart by the start of the id, the first user keeps the name
+    UPDATE users SET name = name || '-' || substr(id, 1, 8)
+        WHERE EXISTS (SELECT 1 FROM users AS other
+                      WHERE other.name = users.name COLLATE NOCASE AND other.rowid < users.rowid);
+    CREATE UNIQUE INDEX users_name ON users(name COLLATE NOCASE);
+    CREATE TABLE logins (
+        token_hash TEXT PRIMARY KEY,
+        user_id TEXT NOT NULL REFERENCES users(id),
+        expires_at INTEGER NOT NULL
+    );
+    "#,
+    r#"
+    ALTER TABLE users ADD COLUMN external_id TEXT;
+    CREATE UNIQUE INDEX users_external_id ON users(external_id);
+    "#,
+    r#"
+    CREATE TABLE exams (
+        id TEXT PRIMARY KEY,
+        opens_at INTEGER NOT NULL,
+        closes_at INTEGER NOT NULL,
+        state TEXT NOT NULL
+    );
+    "#,
+    r#"
+    CREATE TABLE classes (
+        id TEXT PRIMARY KEY,
+        join_code TEXT NOT NULL UNIQUE,
+        created_at INTEGER NOT NULL,
+        state TEXT NOT NULL
+    );
+    "#,
+    r#"
+    CREATE TABLE progress (
+        user_id TEXT PRIMARY KEY REFERENCES users(id),
+        updated_at INTEGER NOT NULL,
+        state TEXT NOT NULL
+    );
+    "#,
+];
+
+/// Attempt as stored in the database
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct AttemptRecord {
+    /// Fingerprint of the answered exercise
+    pub fingerprint: String,
+    /// Session the answer was given in, if any
+    pub session_id: Option<String>,
+    /// Type of the exercise as written in the JSON of the exercise
+    pub exercise_type: String,
+    /// Asked variable as written in the JSON of the exercise
+    pub missing_variable: String,
+    /// Answer as typed by the user
+    pub answer: String,
+    /// Verdict in JSON
+    pub verdict: String,
+    /// Whether the answer was fully correct
+    pub correct: bool,
+    /// Partial credit between 0 and 1
+    pub score: f64,
+    /// When the answer was given, in seconds since the Unix epoch
+    pub answered_at: u64,
+}
+
+/// Name of a unit variant as serde writes it, e.g. "OhmsLaw"
+fn variant_name<T: Serialize>(value: &T) -> Result<String> {
+    Ok(serde_json::to_value(value)?
+        .as_str()
+        .unwrap_or_default()
+        .to_string())
+}
+
+/// SQLite database, shared between threads behind a mutex
+pub struct Storage {
+    connection: Mutex<Connection>,
+}
+
+impl Storage {
+    /// Opens or creates the database file and upgrades its schema
+    pub fn open(path: impl AsRef<Path>) -> Result<Storage> {
+        let path = path.as_ref();
+        let connection = Connection::open(path)
+            .with_context(|| format!("Unable to open database {}", path.display()))?;
+        Storage::migrate(connection)
+    }
+
+    /// Creates a database that only lives in memory, mostly for tests
+    pub fn open_in_memory() -> Result<Storage> {
+        Storage::migrate(Connection::open_in_memory()?)
+    }
+
+    /// Runs the migrations the database has not seen yet
+    fn migrate(mut connection: Connection) -> Result<Storage> {
+        connection.pragma_update(None, "foreign_keys", true)?;
+        let version: usize =
+            connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let transaction = connection.transaction()?;
+            transaction
+                .execute_batch(migration)
+                .with_context(|| format!("Unable to migrate database to version {}", index + 1))?;
+            transaction.pragma_update(None, "user_version", index + 1)?;
+            transaction.commit()?;
+        }
+        Ok(Storage {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// Runs the function with the connection
+    fn with<T>(&self, f: impl FnOnce(&Connection) -> Result<T>) -> Result<T> {
+        let connection = self.connection.lock().unwrap();
+        f(&connection)
+    }
+
+    /// Version of the schema, i.e. how many migrations have been run
+    pub fn schema_version(&self) -> Result<usize> {
+        self.with(|connection| {
+            Ok(connection.pragma_query_value(None, "user_version", |row| row.get(0))?)
+        })
+    }
+
+    /// Adds a user or updates an existing one
+    pub fn save_user(&self, user: &User) -> Result<()> {
+        self.with(|connection| {
+            connection.execute(
+                "INSERT INTO users (id, name, role, password_hash, external_id, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                 ON CONFLICT(id) DO UPDATE SET
+                    name = excluded.name,
+                    role = excluded.role,
+                    password_hash = excluded.password_hash,
+                    external_id = excluded.external_id",
+                params![
+                    user.id,
+                    user.username,
+                    user.role.to_string(),
+                    user.password_hash,
+                    user.external_id,
+                    user.created_at
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Every user in the order they were created
+    pub fn load_users(&self) -> Result<Vec<User>> {
+        self.with(|connection| {
+            let mut statement = connection.prepare(
+                "SELECT id, name, role, password_hash, external_id, created_at
+                 FROM users ORDER BY created_at",
+            )?;
+            let rows = statement
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                        row.get::<_, Option<String>>(4)?,
+                        row.get::<_, u64>(5)?,
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            rows.into_iter()
+                .map(
+                    |(id, username, role, password_hash, external_id, created_at)| {
+                        Ok(User {
+                            role: role.parse().with_context(|| {
+                                format!("User {} in the database is corrupted", id)
+                            })?,
+                            id,
+                            username,
+                            password_hash,
+                            external_id,
+                            created_at,
+                        })
+                    },
+                )
+                .collect()
+        })
+    }
+
+    /// Stores a login
+    pub fn save_login(&self, login: &Login) -> Result<()> {
+        self.with(|connection| {
+            connection.execute(
+                "INSERT INTO logins (token_hash, user_id, expires_at) VALUES (?1, ?2, ?3)",
+                params![login.token_hash, login.user_id, login.expires_at],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Removes a login and every expired one
+    pub fn delete_login(&self, token_hash: &str, now: u64) -> Result<()> {
+        self.with(|connection| {
+            connection.execute(
+                "DELETE FROM logins WHERE token_hash = ?1 OR expires_at <= ?2",
+                params![token_hash, now],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Logins that have not expired
+    pub fn load_logins(&self, now: u64) -> Result<Vec<Login>> {
+        self.with(|connection| {
+            let mut statement = connection.prepare(
+                "SELECT token_hash, user_id, expires_at FROM logins WHERE expires_at > ?1",
+            )?;
+            let logins = statement
+                .query_map([now], |row| {
+                    Ok(Login {
+                        token_hash: row.get(0)?,
+                        user_id: row.get(1)?,
+                        expires_at: row.get(2)?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(logins)
+        })
+    }
+
+    /// Inserts or updates the session together with the user it belongs to
+    pub fn save_session(&self, session: &TrainingSession) -> Result<()> {
+        let state = serde_json::to_string(session)?;
+        self.with(|connection| {
+            connection.execute(
+                "INSERT INTO sessions (id, user_id, started_at, last_activity, state)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT(id) DO UPDATE SET
+                    user_id = COALESCE(excluded.user_id, sessions.user_id),
+                    last_activity = excluded.last_activity,
+                    state = excluded.state",
+                params![
+                    session.id,
+                    session.user_id,
+                    session.started_at,
+                    session.last_activity,
+                    state
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Loads a session, None if it does not exist
+    pub fn load_session(&self, id: &str) -> Result<Option<TrainingSession>> {
+        let state: Option<String> = self.with(|connection| {
+            Ok(connection
+                .query_row("SELECT state FROM sessions WHERE id = ?1", [id], |row| {
+                    row.get(0)
+                })
+                .optional()?)
+        })?;
+        state
+            .map(|state| {
+                serde_json::from_str(&state)
+                    .with_context(|| format!("Session {} in the database is corrupted", id))
+            })
+            .transpose()
+    }
+
+    /// Inserts or updates the exam together with its submissions
+    pub fn save_exam(&self, exam: &Exam) -> Result<()> {
+        let state = serde_json::to_string(exam)?;
+        self.with(|connection| {
+            connection.execute(
+                "INSERT INTO exams (id, opens_at, closes_at, state) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(id) DO UPDATE SET state = excluded.state",
+                params![exam.id, exam.opens_at, exam.closes_at, state],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Loads an exam, None if it does not exist
+    pub fn load_exam(&self, id: &str) -> Result<Option<Exam>> {
+        let state: Option<String> = self.with(|connection| {
+            Ok(connection
+                .query_row("SELECT state FROM exams WHERE id = ?1", [id], |row| {
+                    row.get(0)
+                })
+                .optional()?)
+        })?;
+        state
+            .map(|state| {
+                serde_json::from_str(&state)
+                    .with_context(|| format!("Exam {} in the database is corrupted", id))
+            })
+            .transpose()
+    }
+
+    /// Inserts or updates the class together with its students and assignments
+    pub fn save_class(&self, class: &Classroom) -> Result<()> {
+        let state = serde_json::to_string(class)?;
+        self.with(|connection| {
+            connection.execute(
+                "INSERT INTO classes (id, join_code, created_at, state) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(id) DO UPDATE SET state = excluded.state",
+                params![class.id, class.join_code, class.created_at, state],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Loads a class, None if it does not exist
+    pub fn load_class(&self, id: &str) -> Result<Option<Classroom>> {
+        let state: Option<String> = self.with(|connection| {
+            Ok(connection
+                .query_row("SELECT state FROM classes WHERE id = ?1", [id], |row| {
+                    row.get(0)
+                })
+                .optional()?)
+        })?;
+        state
+            .map(|state| {
+                serde_json::from_str(&state)
+                    .with_context(|| format!("Class {} in the database is corrupted", id))
+            })
+            .transpose()
+    }
+
+    /// Identifier of the class with the join code, None if no class has it
+    pub fn class_id(&self, join_code: &str) -> Result<Option<String>> {
+        self.with(|connection| {
+            Ok(connection
+                .query_row(
+                    "SELECT id FROM classes WHERE join_code = ?1",
+                    [join_code],
+                    |row| row.get(0),
+                )
+                .optional()?)
+        })
+    }
+
+    /// Inserts or updates the progress of a user
+    pub fn save_progress(&self, progress: &Progress) -> Result<()> {
+        let state = serde_json::to_string(progress)?;
+        self.with(|connection| {
+            connection.execute(
+                "INSERT INTO progress (user_id, updated_at, state) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(user_id) DO UPDATE SET
+                    updated_at = excluded.updated_at,
+                    state = excluded.state",
+                params![progress.user_id, progress.updated_at, state],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Loads the progress of a user, None if they have none yet
+    pub fn load_progress(&self, user_id: &str) -> Result<Option<Progress>> {
+        let state: Option<String> = self.with(|connection| {
+            Ok(connection
+                .query_row(
+                    "SELECT state FROM progress WHERE user_id = ?1",
+                    [user_id],
+                    |row| row.get(0),
+                )
+                .optional()?)
+        })?;
+        state
+            .map(|state| {
+                serde_json::from_str(&state).with_context(|| {
+                    format!("Progress of user {} in the database is corrupted", user_id)
+                })
+            })
+            .transpose()
+    }
+
+    /// Records an exercise handed out to a client
+    pub fn record_exercise(
+        &self,
+        exercise: &EquationExercise,
+        session_id: Option<&str>,
+        now: u64,
+    ) -> Result<()> {
+        let json = serde_json::to_string(exercise)?;
+        self.with(|connection| {
+            connection.execute(
+                "INSERT INTO exercises (fingerprint, session_id, exercise, issued_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![exercise.fingerprint(), session_id, json, now],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Records a graded answer
+    pub fn record_attempt(
+        &self,
+        exercise: &EquationExercise,
+        session_id: Option<&str>,
+        answer: &str,
+        grade: &Grade,
+        score: &Score,
+        now: u64,
+    ) -> Result<()> {
+        let exercise_type = variant_name(&exercise.exercise_type)?;
+        let missing_variable = variant_name(&exercise.missing_variable)?;
+        let verdict = serde_json::to_string(&grade.verdict)?;
+        self.with(|connection| {
+            connection.execute(
+                "INSERT INTO attempts (fingerprint, session_id, exercise_type, missing_variable,
+                    answer, verdict, correct, score, answered_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    exercise.fingerprint(),
+                    session_id,
+                    exercise_type,
+                    missing_variable,
+                    answer,
+                    verdict,
+                    grade.is_correct(),
+                    score.value,
+                    now
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Attempts made in the session, oldest first
+    pub fn session_attempts(&self, session_id: &str) -> Result<Vec<AttemptRecord>> {
+        self.with(|connection| {
+            let mut statement = connection.prepare(
+                "SELECT fingerprint, session_id, exercise_type, missing_variable, answer,
+                    verdict, correct, score, answered_at
+                 FROM attempts WHERE session_id = ?1 ORDER BY id",
+            )?;
+            let attempts = statement
+                .query_map([session_id], |row| {
+                    Ok(AttemptRecord {
+                        fingerprint: row.get(0)?,
+                        session_id: row.get(1)?,
+                        exercise_type: row.get(2)?,
+                        missing_variable: row.get(3)?,
+                        answer: row.get(4)?,
+                        verdict: row.get(5)?,
+                        correct: row.get(6)?,
+                        score: row.get(7)?,
+                        answered_at: row.get(8)?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(attempts)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::Role;
+    use crate::answer::ParsedAnswer;
+    use crate::difficulty::Difficulty;
+    use crate::equations::EquationExerciseBuilder;
+    use crate::exam::ExamSpec;
+    use crate::grading::GradingPolicy;
+    use crate::scoring::ScoringModel;
+    use crate::spec::{ExerciseSetSpec, ExerciseSpec};
+
+    #[test]
+    fn test_migrations() {
+        let storage = Storage::open_in_memory().unwrap();
+        assert_eq!(storage.schema_version().unwrap(), MIGRATIONS.len());
+
+        // opening an up to date database again runs nothing
+        let path = std::env::temp_dir().join(format!("puimuri-test-{}.sqlite", std::process::id()));
+        Storage::open(&path).unwrap();
+        let storage = Storage::open(&path).unwrap();
+        assert_eq!(storage.schema_version().unwrap(), MIGRATIONS.len());
+        drop(storage);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_names_differing_in_case() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch(MIGRATIONS[0]).unwrap();
+        connection.pragma_update(None, "user_version", 1).unwrap();
+        for (id, name) in [("1a", "Ada"), ("2b", "ada"), ("3c", "ADA"), ("4d", "Bob")] {
+            connection
+                .execute(
+                    "INSERT INTO users (id, name, created_at) VALUES (?1, ?2, 0)",
+                    params![id, name],
+                )
+                .unwrap();
+        }
+        let storage = Storage::migrate(connection).unwrap();
+        let mut names: Vec<String> = storage
+            .load_users()
+            .unwrap()
+            .into_iter()
+            .map(|user| user.username)
+            .collect();
+        names.sort();
+        assert_eq!(names, ["ADA-3c", "Ada", "Bob", "ada-2b"]);
+    }
+
+    fn user(id: &str, username: &str) -> User {
+        User {
+            id: id.to_string(),
+            username: username.to_string(),
+            role: Role::Teacher,
+            password_hash: "$argon2id$hash".to_string(),
+            external_id: Some(format!("idp#{}", id)),
+            created_at: 5,
+        }
+    }
+
+    #[test]
+    fn test_users_and_logins() {
+        let storage = Storage::open_in_memory().unwrap();
+        let mut ada = user("1", "Ada");
+        storage.save_user(&ada).unwrap();
+        ada.role = Role::Admin;
+        storage.save_user(&ada).unwrap();
+        assert_eq!(storage.load_users().unwrap(), vec![ada]);
+        // usernames are unique regardless of case
+        assert!(storage.save_user(&user("2", "ADA")).is_err());
+
+        let login = Login {
+            token_hash: "hash".to_string(),
+            user_id: "1".to_string(),
+            expires_at: 100,
+        };
+        storage.save_login(&login).unwrap();
+        assert_eq!(storage.load_logins(99).unwrap(), vec![login]);
+        assert!(storage.load_logins(100).unwrap().is_empty());
+        storage.delete_login("hash", 0).unwrap();
+        assert!(storage.load_logins(0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sessions_round_trip() {
+        let storage = Storage::open_in_memory().unwrap();
+        storage.save_user(&user("user", "Ada")).unwrap();
+        let mut session = TrainingSession::new(ExerciseSpec::default(), 10);
+        session.user_id = Some("user".to_string());
+        storage.save_session(&session).unwrap();
+        assert_eq!(
+            storage.load_session(&session.id).unwrap(),
+            Some(session.clone())
+        );
+
+        session.next_exercise(20).unwrap();
+        storage.save_session(&session).unwrap();
+        assert_eq!(storage.load_session(&session.id).unwrap(), Some(session));
+        assert_eq!(storage.load_session("missing").unwrap(), None);
+
+        let mut unknown_user = TrainingSession::new(ExerciseSpec::default(), 10);
+        unknown_user.user_id = Some("nobody".to_string());
+        assert!(storage.save_session(&unknown_user).is_err());
+    }
+
+    #[test]
+    fn test_exams_round_trip() {
+        let storage = Storage::open_in_memory().unwrap();
+        let spec = ExamSpec {
+            set: ExerciseSetSpec {
+                count: 5,
+                ..ExerciseSetSpec::default()
+            },
+            time_limit: 600,
+            shuffle: false,
+        };
+        let mut exam = Exam::new(spec, 10).unwrap();
+        storage.save_exam(&exam).unwrap();
+        assert_eq!(storage.load_exam(&exam.id).unwrap(), Some(exam.clone()));
+
+        let policy = GradingPolicy::default();
+        exam.submit("Ada", &[], policy, &ScoringModel::default(), 20)
+            .unwrap();
+        storage.save_exam(&exam).unwrap();
+        assert_eq!(storage.load_exam(&exam.id).unwrap(), Some(exam));
+        assert_eq!(storage.load_exam("missing").unwrap(), None);
+    }
+
+    #[test]
+    fn test_classes_round_trip() {
+        let storage = Storage::open_in_memory().unwrap();
+        let mut class = Classroom::new("Electricians", 10);
+        storage.save_class(&class).unwrap();
+        class.join("Ada", 20).unwrap();
+        storage.save_class(&class).unwrap();
+        assert_eq!(storage.load_class(&class.id).unwrap(), Some(class.clone()));
+        assert_eq!(storage.load_class("missing").unwrap(), None);
+        assert_eq!(storage.class_id(&class.join_code).unwrap(), Some(class.id));
+        assert_eq!(storage.class_id("000000").unwrap(), None);
+    }
+
+    #[test]
+    fn test_progress_round_trip() {
+        let storage = Storage::open_in_memory().unwrap();
+        storage.save_user(&user("1", "Ada")).unwrap();
+        let mut progress = Progress::new("1", 10);
+        storage.save_progress(&progress).unwrap();
+        let exercise = EquationExerciseBuilder::new().build().unwrap();
+        progress.record(&exercise, true, Difficulty::default(), 20);
+        storage.save_progress(&progress).unwrap();
+        assert_eq!(storage.load_progress("1").unwrap(), Some(progress));
+        assert_eq!(storage.load_progress("missing").unwrap(), None);
+    }
+
+    #[test]
+    fn test_record_attempts() {
+        let storage = Storage::open_in_memory().unwrap();
+        let session = TrainingSession::new(ExerciseSpec::default(), 0);
+        storage.save_session(&session).unwrap();
+
+        let exercise = EquationExerciseBuilder::new()
+            .build_with_random_exercisetype()
+            .unwrap();
+        storage
+            .record_exercise(&exercise, Some(&session.id), 1)
+            .unwrap();
+        storage.record_exercise(&exercise, None, 1).unwrap();
+
+        let answer = ParsedAnswer::from(exercise.solve().unwrap().answer);
+        let grade = exercise.grade(&answer, None).unwrap();
+        let score = ScoringModel::default().score(&grade, &answer);
+        storage
+            .record_attempt(&exercise, Some(&session.id), "42", &grade, &score, 2)
+            .unwrap();
+        storage
+            .record_attempt(&exercise, None, "42", &grade, &score, 3)
+            .unwrap();
+
+        let attempts = storage.session_attempts(&session.id).unwrap();
+        assert_eq!(attempts.len(), 1);
+        assert_eq!(attempts[0].fingerprint, exercise.fingerprint());
+        assert_eq!(attempts[0].answer, "42");
+        assert!(attempts[0].correct);
+        assert_eq!(attempts[0].verdict, r#"{"kind":"Correct"}"#);
+        assert_eq!(attempts[0].answered_at, 2);
+    }
+}
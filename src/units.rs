@@ -0,0 +1,199 @@
+#![warn(missing_docs)]
+
+//! PUImURI trainer for Ohms law, power equation and their combinations
+//!
+//! SI prefix handling so that values can be presented and answered as "4.7 kΩ" or "2 mA"
+
+use serde::{Deserialize, Serialize};
+
+use crate::equations::{EquationUnit, EquationVariable};
+
+/// SI prefix that a value is presented or requested in
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum SiPrefix {
+    /// Mega, 10^6
+    Mega,
+    /// Kilo, 10^3
+    Kilo,
+    /// By default values are in their base unit without a prefix
+    #[default]
+    Base,
+    /// Milli, 10^-3
+    Milli,
+    /// Micro, 10^-6
+    Micro,
+}
+
+impl SiPrefix {
+    /// All supported prefixes from the largest to the smallest
+    pub const ALL: [SiPrefix; 5] = [
+        SiPrefix::Mega,
+        SiPrefix::Kilo,
+        SiPrefix::Base,
+        SiPrefix::Milli,
+        SiPrefix::Micro,
+    ];
+
+    /// Power of ten this prefix stands for
+    pub fn exponent(&self) -> i32 {
+        match self {
+            SiPrefix::Mega => 6,
+            SiPrefix::Kilo => 3,
+            SiPrefix::Base => 0,
+            SiPrefix::Milli => -3,
+            SiPrefix::Micro => -6,
+        }
+    }
+
+    /// Multiplier that converts a prefixed value into the base unit
+    pub fn factor(&self) -> f64 {
+        10f64.powi(self.exponent())
+    }
+
+    /// Symbol of the prefix as it is written in front of the unit
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            SiPrefix::Mega => "M",
+            SiPrefix::Kilo => "k",
+            SiPrefix::Base => "",
+            SiPrefix::Milli => "m",
+            SiPrefix::Micro => "µ",
+        }
+    }
+
+    /// Converts a value written with this prefix into the base unit
+    pub fn to_base(&self, value: f64) -> f64 {
+        // dividing by an exact power of ten keeps values like 4.7 mA as 0.0047 instead of 0.0047000000000000005
+        let exponent = self.exponent();
+        if exponent >= 0 {
+            value * 10f64.powi(exponent)
+        } else {
+            value / 10f64.powi(-exponent)
+        }
+    }
+
+    /// Converts a value in the base unit into a value written with this prefix
+    pub fn from_base(&self, value: f64) -> f64 {
+        let exponent = self.exponent();
+        if exponent >= 0 {
+            value / 10f64.powi(exponent)
+        } else {
+            value * 10f64.powi(-exponent)
+        }
+    }
+
+    /// Picks the prefix that presents the value with an integer part between 1 and 999
+    pub fn best_for(value: f64) -> SiPrefix {
+        let magnitude = value.abs();
+        if magnitude == 0.0 || !magnitude.is_finite() {
+            return SiPrefix::Base;
+        }
+        SiPrefix::ALL
+            .into_iter()
+            .find(|prefix| magnitude >= prefix.factor())
+            .unwrap_or(SiPrefix::Micro)
+    }
+
+    /// Prefixes that are used for a variable in everyday electrical work
+    pub fn common_for(variable: EquationVariable) -> &'static [SiPrefix] {
+        match variable {
+            EquationVariable::Voltage => &[SiPrefix::Kilo, SiPrefix::Base, SiPrefix::Milli],
+            EquationVariable::Current => &[SiPrefix::Base, SiPrefix::Milli, SiPrefix::Micro],
+            EquationVariable::Resistance => &[SiPrefix::Mega, SiPrefix::Kilo, SiPrefix::Base],
+            EquationVariable::Power => &[SiPrefix::Kilo, SiPrefix::Base, SiPrefix::Milli],
+        }
+    }
+
+    /// Picks the best prefix for the value from the prefixes that are common for the variable
+    pub fn best_for_variable(variable: EquationVariable, value: f64) -> SiPrefix {
+        let best = SiPrefix::best_for(value);
+        let common = SiPrefix::common_for(variable);
+        if common.contains(&best) {
+            return best;
+        }
+        // fall back to the closest common prefix
+        *common
+            .iter()
+            .min_by_key(|prefix| (prefix.exponent() - best.exponent()).abs())
+            .unwrap()
+    }
+}
+
+/// Rounds the value to the given amount of decimals
+pub fn round_to(value: f64, decimals: u32) -> f64 {
+    let multiplier = 10f64.powi(decimals as i32);
+    (value * multiplier).round() / multiplier
+}
+
+/// Formats a value in base units with the prefix and unit symbol, e.g. "4.7 kΩ"
+pub fn format_quantity(value: f64, prefix: SiPrefix, unit: EquationUnit) -> String {
+    // round away floating point noise such as 42.50000000000001 before printing
+    let prefixed = format!("{:.9}", prefix.from_base(value))
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string();
+    format!("{} {}{}", prefixed, prefix.symbol(), unit.symbol())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_prefix_conversions() {
+        assert_eq!(SiPrefix::Kilo.to_base(4.7), 4700.0);
+        assert_eq!(SiPrefix::Milli.to_base(4.7), 0.0047);
+        assert_eq!(SiPrefix::Micro.to_base(2.0), 0.000002);
+        assert_relative_eq!(SiPrefix::Milli.from_base(0.0425), 42.5, epsilon = 1e-9);
+        assert_eq!(SiPrefix::Base.to_base(12.0), 12.0);
+    }
+
+    #[test]
+    fn test_best_prefix() {
+        assert_eq!(SiPrefix::best_for(4700.0), SiPrefix::Kilo);
+        assert_eq!(SiPrefix::best_for(0.002), SiPrefix::Milli);
+        assert_eq!(SiPrefix::best_for(12.0), SiPrefix::Base);
+        assert_eq!(SiPrefix::best_for(2_200_000.0), SiPrefix::Mega);
+        assert_eq!(SiPrefix::best_for(0.0), SiPrefix::Base);
+    }
+
+    #[test]
+    fn test_best_prefix_for_variable() {
+        assert_eq!(
+            SiPrefix::best_for_variable(EquationVariable::Current, 4700.0),
+            SiPrefix::Base
+        );
+        assert_eq!(
+            SiPrefix::best_for_variable(EquationVariable::Resistance, 0.5),
+            SiPrefix::Base
+        );
+        assert_eq!(
+            SiPrefix::best_for_variable(EquationVariable::Power, 2400.0),
+            SiPrefix::Kilo
+        );
+    }
+
+    #[test]
+    fn test_format_quantity() {
+        assert_eq!(
+            format_quantity(4700.0, SiPrefix::Kilo, EquationUnit::Ohm),
+            "4.7 kΩ"
+        );
+        assert_eq!(
+            format_quantity(0.002, SiPrefix::Milli, EquationUnit::Ampere),
+            "2 mA"
+        );
+        assert_eq!(
+            format_quantity(230.0, SiPrefix::Base, EquationUnit::Volt),
+            "230 V"
+        );
+    }
+
+    #[test]
+    fn test_round_to() {
+        assert_eq!(round_to(4.6666, 1), 4.7);
+        assert_eq!(round_to(4.6666, 0), 5.0);
+        assert_eq!(round_to(0.125, 2), 0.13);
+    }
+}